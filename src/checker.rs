@@ -0,0 +1,580 @@
+use crate::data::ast::{Expression, ExpressionItem, FunctionDefinition, Statement, StatementItem};
+use crate::data::tokens::Token;
+use crate::data::types::Type;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// What the checker knows about a name
+#[derive(Clone)]
+enum Symbol {
+    Variable(Type),
+    // a function declared with `fun`, whose calls are checked against its parameters
+    Function(Rc<FunctionDefinition>),
+}
+
+/// The type of an expression's value, and whether an annotation went into working it out
+#[derive(Clone, Copy)]
+struct Checked {
+    kind: Type,
+    annotated: bool,
+}
+
+impl Checked {
+    fn new(kind: Type, annotated: bool) -> Self {
+        Checked { kind, annotated }
+    }
+
+    fn any() -> Self {
+        Checked::new(Type::Any, false)
+    }
+}
+
+/// Static pass run between parsing and resolving that checks the optional type annotations.
+/// Values given to annotated names, parameters and return types have to fit the annotation,
+/// and operators have to fit the types of operands that have an annotation behind them.
+/// Code without annotations is left to fail at run time, where it can still be caught
+pub struct Checker {
+    scopes: Vec<HashMap<String, Symbol>>,
+    // the return types of the functions enclosing the statement being checked, innermost last
+    returns: Vec<Type>,
+    errors: Vec<String>,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Checker {
+            scopes: vec![HashMap::new()],
+            returns: vec![],
+            errors: vec![],
+        }
+    }
+
+    pub fn check(&mut self, statements: &[Statement]) -> Result<(), Vec<String>> {
+        self.check_statements(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        let line = statement.line();
+        match statement.item() {
+            StatementItem::ExpressionStatement(expr)
+            | StatementItem::PrintStatement(expr)
+            | StatementItem::Throw(expr) => {
+                self.check_expression(expr);
+            }
+            StatementItem::Declaration {
+                pattern,
+                initializer,
+                annotation,
+                ..
+            } => {
+                let initial = self.check_expression(initializer).kind;
+                match annotation {
+                    Some(annotation) => {
+                        let name = &pattern.names()[0];
+                        if !initial.fits(*annotation) {
+                            self.errors.push(format!(
+                                "Line {}: Type Error: cannot initialize '{}' of type {} with {}",
+                                line, name, annotation, initial
+                            ));
+                        }
+                        self.declare(name, Symbol::Variable(*annotation));
+                    }
+                    None => {
+                        for name in pattern.names() {
+                            self.declare(name, Symbol::Variable(Type::Any));
+                        }
+                    }
+                }
+            }
+            StatementItem::Block { statements } => {
+                self.begin_scope();
+                self.check_statements(statements);
+                self.end_scope();
+            }
+            StatementItem::IfStatement {
+                test,
+                when_true,
+                when_false,
+            } => {
+                self.check_expression(test);
+                self.check_statement(when_true);
+                if let Some(when_false) = when_false {
+                    self.check_statement(when_false);
+                }
+            }
+            StatementItem::WhileStatement {
+                test,
+                body,
+                increment,
+                ..
+            } => {
+                self.check_expression(test);
+                self.check_statement(body);
+                if let Some(increment) = increment {
+                    self.check_expression(increment);
+                }
+            }
+            StatementItem::ForIn {
+                pattern,
+                iterable,
+                body,
+                ..
+            } => {
+                self.check_expression(iterable);
+                self.begin_scope();
+                for name in pattern.names() {
+                    self.declare(name, Symbol::Variable(Type::Any));
+                }
+                self.check_statement(body);
+                self.end_scope();
+            }
+            StatementItem::Break { .. } | StatementItem::Continue { .. } => (),
+            StatementItem::FunctionDeclaration(definition) => {
+                self.declare(&definition.name, Symbol::Function(definition.clone()));
+                self.check_function(definition);
+            }
+            StatementItem::ReturnStatement(value) => {
+                let returned = match value {
+                    Some(value) => self.check_expression(value).kind,
+                    None => Type::Nil,
+                };
+                if let Some(expected) = self.returns.last().copied() {
+                    if !returned.fits(expected) {
+                        self.errors.push(format!(
+                            "Line {}: Type Error: cannot return {} from a function returning {}",
+                            line, returned, expected
+                        ));
+                    }
+                }
+            }
+            StatementItem::ClassDeclaration {
+                name,
+                superclass,
+                methods,
+            } => {
+                if let Some(superclass) = superclass {
+                    self.check_expression(superclass);
+                }
+                self.declare(name, Symbol::Variable(Type::Any));
+                for method in methods {
+                    self.check_function(method);
+                }
+            }
+            StatementItem::EnumDeclaration(enumeration) => {
+                self.declare(&enumeration.name, Symbol::Variable(Type::Any));
+            }
+            StatementItem::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.begin_scope();
+                self.check_statements(body);
+                self.end_scope();
+                if let Some((name, handler)) = catch {
+                    self.begin_scope();
+                    self.declare(name, Symbol::Variable(Type::Any));
+                    self.check_statements(handler);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.check_statements(finally);
+                    self.end_scope();
+                }
+            }
+            StatementItem::Import { name, .. } => {
+                self.declare(name, Symbol::Variable(Type::Any));
+            }
+            StatementItem::Export(declaration) => self.check_statement(declaration),
+            StatementItem::Match { subject, arms } => {
+                self.check_expression(subject);
+                for arm in arms {
+                    self.begin_scope();
+                    for name in arm.pattern.names() {
+                        self.declare(name, Symbol::Variable(Type::Any));
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.check_expression(guard);
+                    }
+                    self.check_statement(&arm.body);
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    fn check_function(&mut self, definition: &FunctionDefinition) {
+        self.begin_scope();
+        for param in definition.params.iter() {
+            let declared = param.annotation.unwrap_or(Type::Any);
+            if let Some(default) = &param.default {
+                let given = self.check_expression(default).kind;
+                if !given.fits(declared) {
+                    self.errors.push(format!(
+                        "Line {}: Type Error: the default value of '{}' is {} but the parameter is {}",
+                        default.line(),
+                        param.name,
+                        given,
+                        declared
+                    ));
+                }
+            }
+            self.declare(&param.name, Symbol::Variable(declared));
+        }
+        if let Some(rest) = &definition.rest {
+            self.declare(rest, Symbol::Variable(Type::Any));
+        }
+        // a generator's return statements finish it rather than giving a value to the caller
+        self.returns.push(if definition.is_generator {
+            Type::Any
+        } else {
+            definition.return_type.unwrap_or(Type::Any)
+        });
+        self.check_statements(&definition.body);
+        self.returns.pop();
+        self.end_scope();
+    }
+
+    /// Check an expression, giving the type of its value
+    fn check_expression(&mut self, expression: &Expression) -> Checked {
+        let line = expression.line();
+        match expression.item() {
+            ExpressionItem::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.check_expression(left);
+                let right = self.check_expression(right);
+                self.check_binary(operator, left, right, line)
+            }
+            ExpressionItem::Logical { left, right, .. } => {
+                let left = self.check_expression(left);
+                let right = self.check_expression(right);
+                join(left, right)
+            }
+            ExpressionItem::Grouping { expression } => self.check_expression(expression),
+            ExpressionItem::Literal { value } => Checked::new(Type::of(value), false),
+            ExpressionItem::Unary { operator, operand } => {
+                let operand = self.check_expression(operand);
+                let kind = operand.kind;
+                match operator {
+                    Token::Bang => Checked::new(Type::Bool, operand.annotated),
+                    // unary plus gives back any value unchanged
+                    Token::Plus => operand,
+                    Token::Tilde if kind == Type::Int || kind == Type::Any => {
+                        Checked::new(Type::Int, operand.annotated)
+                    }
+                    Token::Minus if kind.is_numeric() || kind == Type::Any => operand,
+                    _ if operand.annotated => {
+                        self.errors.push(format!(
+                            "Line {}: Type Error: cannot apply {} to {}",
+                            line, operator, kind
+                        ));
+                        Checked::any()
+                    }
+                    _ => Checked::any(),
+                }
+            }
+            ExpressionItem::Ternary {
+                test,
+                when_true,
+                when_false,
+            } => {
+                self.check_expression(test);
+                let when_true = self.check_expression(when_true);
+                let when_false = self.check_expression(when_false);
+                join(when_true, when_false)
+            }
+            // unannotated names are `Any`, so only annotated ones have a type here
+            ExpressionItem::Variable { name, .. } => match self.look_up(name) {
+                Some(Symbol::Variable(declared)) => Checked::new(declared, declared != Type::Any),
+                _ => Checked::any(),
+            },
+            ExpressionItem::Assignment { name, value, .. } => {
+                let assigned = self.check_expression(value);
+                self.check_assignment(name, assigned.kind, line);
+                assigned
+            }
+            ExpressionItem::Call {
+                callee,
+                arguments,
+                named,
+            } => {
+                self.check_expression(callee);
+                let positional = arguments
+                    .iter()
+                    .map(|argument| self.check_expression(argument).kind)
+                    .collect::<Vec<_>>();
+                let named = named
+                    .iter()
+                    .map(|(name, argument)| (name, self.check_expression(argument).kind))
+                    .collect::<Vec<_>>();
+                let definition = match callee.item() {
+                    ExpressionItem::Variable { name, .. } => match self.look_up(name) {
+                        Some(Symbol::Function(definition)) => definition,
+                        _ => return Checked::any(),
+                    },
+                    _ => return Checked::any(),
+                };
+                let given = definition
+                    .params
+                    .iter()
+                    .zip(positional)
+                    .chain(named.into_iter().filter_map(|(name, given)| {
+                        let param = definition.params.iter().find(|param| param.name == *name)?;
+                        Some((param, given))
+                    }));
+                for (param, given) in given {
+                    let declared = param.annotation.unwrap_or(Type::Any);
+                    if !given.fits(declared) {
+                        self.errors.push(format!(
+                            "Line {}: Type Error: argument '{}' to {} should be {} but got {}",
+                            line, param.name, definition.name, declared, given
+                        ));
+                    }
+                }
+                match definition.return_type {
+                    Some(returned) if !definition.is_generator => Checked::new(returned, true),
+                    _ => Checked::any(),
+                }
+            }
+            ExpressionItem::Get { object, .. } => {
+                self.check_expression(object);
+                Checked::any()
+            }
+            ExpressionItem::Set { object, value, .. } => {
+                self.check_expression(object);
+                self.check_expression(value)
+            }
+            ExpressionItem::List { elements } => {
+                for element in elements {
+                    self.check_expression(element);
+                }
+                Checked::any()
+            }
+            ExpressionItem::Map { entries } => {
+                for (key, value) in entries {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+                Checked::any()
+            }
+            ExpressionItem::Interpolation { parts } => {
+                for part in parts {
+                    self.check_expression(part);
+                }
+                Checked::new(Type::Str, false)
+            }
+            ExpressionItem::Index { object, index } => {
+                self.check_expression(object);
+                self.check_expression(index);
+                Checked::any()
+            }
+            ExpressionItem::IndexSet {
+                object,
+                index,
+                value,
+            } => {
+                self.check_expression(object);
+                self.check_expression(index);
+                self.check_expression(value)
+            }
+            ExpressionItem::Slice { object, start, end } => {
+                self.check_expression(object);
+                for bound in start.iter().chain(end.iter()) {
+                    self.check_expression(bound);
+                }
+                Checked::any()
+            }
+            ExpressionItem::Range { start, end, .. } => {
+                let start = self.check_expression(start);
+                let end = self.check_expression(end);
+                let is_integer = |bound: Type| bound == Type::Int || bound == Type::Any;
+                if (start.annotated || end.annotated)
+                    && (!is_integer(start.kind) || !is_integer(end.kind))
+                {
+                    self.errors.push(format!(
+                        "Line {}: Type Error: range bounds must be integers but got {} and {}",
+                        line, start.kind, end.kind
+                    ));
+                }
+                Checked::any()
+            }
+            ExpressionItem::CompoundAssignment {
+                target,
+                operator,
+                value,
+            } => {
+                let current = self.check_expression(target);
+                let value = self.check_expression(value);
+                let updated = self.check_binary(operator, current, value, line);
+                if let ExpressionItem::Variable { name, .. } = target.item() {
+                    self.check_assignment(name, updated.kind, line);
+                }
+                updated
+            }
+            ExpressionItem::Increment {
+                target, operator, ..
+            } => {
+                let current = self.check_expression(target);
+                let step = Checked::new(Type::Int, false);
+                let updated = self.check_binary(operator, current, step, line);
+                if let ExpressionItem::Variable { name, .. } = target.item() {
+                    self.check_assignment(name, updated.kind, line);
+                }
+                updated
+            }
+            ExpressionItem::Lambda(definition) => {
+                self.check_function(definition);
+                Checked::any()
+            }
+            ExpressionItem::ListAssignment { targets, value } => {
+                for target in targets {
+                    self.check_expression(target);
+                    if let ExpressionItem::Variable { name, .. } = target.item() {
+                        self.check_assignment(name, Type::Any, line);
+                    }
+                }
+                self.check_expression(value);
+                Checked::any()
+            }
+            ExpressionItem::Yield(value) => {
+                if let Some(value) = value {
+                    self.check_expression(value);
+                }
+                Checked::any()
+            }
+            ExpressionItem::This { .. } | ExpressionItem::Super { .. } => Checked::any(),
+        }
+    }
+
+    /// The type of applying a binary operator, following the rules of the interpreter's binary operations.
+    /// Mismatches are only reported when one of the operands is annotated
+    fn check_binary(&mut self, operator: &Token, left: Checked, right: Checked, line: usize) -> Checked {
+        let annotated = left.annotated || right.annotated;
+        let (left, right) = (left.kind, right.kind);
+        let numeric = |operand: Type| operand.is_numeric() || operand == Type::Any;
+        let integer = |operand: Type| operand == Type::Int || operand == Type::Any;
+        // the type of arithmetic on two numbers, when the result is an integer for integer operands
+        let arithmetic = match (left, right) {
+            (Type::Int, Type::Int) => Type::Int,
+            (left, right) if left.is_numeric() && right.is_numeric() => Type::Num,
+            _ => Type::Any,
+        };
+        let (valid, result) = match operator {
+            Token::EqualEqual | Token::BangEqual => (true, Type::Bool),
+            Token::Plus => match left {
+                Type::Str => (true, Type::Str),
+                Type::Any => (true, Type::Any),
+                _ => (numeric(left) && numeric(right), arithmetic),
+            },
+            Token::Minus | Token::Star | Token::Percent | Token::TildeSlash => {
+                (numeric(left) && numeric(right), arithmetic)
+            }
+            Token::Slash | Token::StarStar => (
+                numeric(left) && numeric(right),
+                // integer division and powers can give either kind of number
+                match arithmetic {
+                    Type::Int if matches!(operator, Token::Slash) => Type::Num,
+                    Type::Int => Type::Any,
+                    other => other,
+                },
+            ),
+            Token::Greater | Token::GreaterEqual | Token::Lesser | Token::LesserEqual => {
+                (numeric(left) && numeric(right), Type::Bool)
+            }
+            Token::Ampersand
+            | Token::Pipe
+            | Token::Caret
+            | Token::LesserLesser
+            | Token::GreaterGreater => (integer(left) && integer(right), Type::Int),
+            _ => (true, Type::Any),
+        };
+        if !valid {
+            if annotated {
+                self.errors.push(format!(
+                    "Line {}: Type Error: {} cannot be applied to {} and {}",
+                    line, operator, left, right
+                ));
+            }
+            return Checked::any();
+        }
+        Checked::new(result, annotated)
+    }
+
+    fn check_assignment(&mut self, name: &str, assigned: Type, line: usize) {
+        match self.look_up(name) {
+            Some(Symbol::Variable(declared)) if !assigned.fits(declared) => {
+                self.errors.push(format!(
+                    "Line {}: Type Error: cannot assign {} to '{}' of type {}",
+                    line, assigned, name, declared
+                ));
+            }
+            // the name may not hold that function any more, so its calls can't be checked
+            Some(Symbol::Function(_)) => self.forget_function(name),
+            _ => (),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, symbol: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), symbol);
+        }
+    }
+
+    fn forget_function(&mut self, name: &str) {
+        if let Some(symbol) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+        {
+            *symbol = Symbol::Variable(Type::Any);
+        }
+    }
+
+    fn look_up(&self, name: &str) -> Option<Symbol> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+}
+
+/// The type of a value that comes from one of two expressions
+fn join(left: Checked, right: Checked) -> Checked {
+    let annotated = left.annotated || right.annotated;
+    if left.kind == right.kind {
+        Checked::new(left.kind, annotated)
+    } else {
+        Checked::new(Type::Any, annotated)
+    }
+}
@@ -1,7 +1,10 @@
+use super::enums::LoxEnum;
 use super::literals::Literal;
 use super::tokens::Token;
+use super::types::Type;
 use super::meta::MetaContainer;
 use std::fmt;
+use std::rc::Rc;
 
 #[derive(Clone)]
 pub enum ExpressionItem {
@@ -11,8 +14,33 @@ pub enum ExpressionItem {
     Literal { value: Literal },
     Unary { operator: Token, operand: Box<Expression> },
     Ternary { test: Box<Expression>, when_true: Box<Expression>, when_false: Box<Expression> },
-    Variable { name: String },
-    Assignment { name: String, value: Box<Expression> }
+    // depth is the number of scopes between the use and the declaration,
+    // filled in by the resolver; None means the name is global
+    Variable { name: String, depth: Option<usize> },
+    Assignment { name: String, value: Box<Expression>, depth: Option<usize> },
+    Call { callee: Box<Expression>, arguments: Vec<Expression>, named: Vec<(String, Expression)> },
+    Get { object: Box<Expression>, name: String },
+    Set { object: Box<Expression>, name: String, value: Box<Expression> },
+    List { elements: Vec<Expression> },
+    Map { entries: Vec<(Expression, Expression)> },
+    Interpolation { parts: Vec<Expression> },
+    Index { object: Box<Expression>, index: Box<Expression> },
+    IndexSet { object: Box<Expression>, index: Box<Expression>, value: Box<Expression> },
+    Slice { object: Box<Expression>, start: Option<Box<Expression>>, end: Option<Box<Expression>> },
+    // `start..end`, or `start..=end` when inclusive
+    Range { start: Box<Expression>, end: Box<Expression>, inclusive: bool },
+    // `target op= value`, where operator is the binary operator applied
+    CompoundAssignment { target: Box<Expression>, operator: Token, value: Box<Expression> },
+    // `++target`, `target--` etc, where operator is `+` or `-`
+    Increment { target: Box<Expression>, operator: Token, prefix: bool },
+    // an anonymous function, from `fun (...) {...}` or `(...) => <expression>`
+    Lambda(Rc<FunctionDefinition>),
+    // `[a, b] = value`, where each target is a variable, property, index or nested list of targets
+    ListAssignment { targets: Vec<Expression>, value: Box<Expression> },
+    // suspends the enclosing generator, and evaluates to the value it is resumed with
+    Yield(Option<Box<Expression>>),
+    This { depth: Option<usize> },
+    Super { method: String, depth: Option<usize> }
 }
 
 impl fmt::Debug for ExpressionItem {
@@ -25,8 +53,26 @@ impl fmt::Debug for ExpressionItem {
             Literal { value } => write!(f, "(literal {})", value),
             Unary {operator, operand} => write!(f, "({} {:?})", operator, operand),
             Ternary {test, when_true, when_false} => write!(f, "(?: {:?} {:?} {:?})", test, when_true, when_false),
-            Variable { name } => write!(f, "(var {})", name),
-            Assignment { name, value } => write!(f, "(set! {} {:?})", name, value)
+            Variable { name, .. } => write!(f, "(var {})", name),
+            Assignment { name, value, .. } => write!(f, "(set! {} {:?})", name, value),
+            Call { callee, arguments, named } => write!(f, "(call {:?} {:?} {:?})", callee, arguments, named),
+            Get { object, name } => write!(f, "(. {:?} {})", object, name),
+            Set { object, name, value } => write!(f, "(set! (. {:?} {}) {:?})", object, name, value),
+            List { elements } => write!(f, "(list {:?})", elements),
+            Map { entries } => write!(f, "(map {:?})", entries),
+            Interpolation { parts } => write!(f, "(interpolate {:?})", parts),
+            Index { object, index } => write!(f, "([] {:?} {:?})", object, index),
+            IndexSet { object, index, value } => write!(f, "(set! ([] {:?} {:?}) {:?})", object, index, value),
+            Slice { object, start, end } => write!(f, "([:] {:?} {:?} {:?})", object, start, end),
+            Range { start, end, inclusive } => write!(f, "({} {:?} {:?})", if *inclusive { "..=" } else { ".." }, start, end),
+            CompoundAssignment { target, operator, value } => write!(f, "({}= {:?} {:?})", operator, target, value),
+            Increment { target, operator, prefix: true } => write!(f, "({}{} {:?})", operator, operator, target),
+            Increment { target, operator, prefix: false } => write!(f, "({:?} {}{})", target, operator, operator),
+            ListAssignment { targets, value } => write!(f, "(set! {:?} {:?})", targets, value),
+            Yield(value) => write!(f, "(yield {:?})", value),
+            Lambda(definition) => write!(f, "(lambda {:?} {:?})", definition.params, definition.body),
+            This { .. } => write!(f, "this"),
+            Super { method, .. } => write!(f, "(. super {})", method)
         }
     }
 }
@@ -35,10 +81,129 @@ impl fmt::Debug for ExpressionItem {
 pub enum StatementItem {
     ExpressionStatement(Expression),
     PrintStatement(Expression),
-    Declaration { name: String, initializer: Expression },
+    // `var` or, when constant, `const`; only a single name can be annotated with a type
+    Declaration { pattern: Pattern, initializer: Expression, constant: bool, annotation: Option<Type> },
     Block { statements: Vec<Statement> },
     IfStatement { test: Expression, when_true: Box<Statement>, when_false: Option<Box<Statement>> },
-    WhileStatement { test: Expression, body: Box<Statement> }
+    // increment runs after every iteration of the body, even when it is cut short by `continue`
+    WhileStatement { test: Expression, body: Box<Statement>, increment: Option<Expression>, label: Option<String> },
+    // `for (<pattern> in <iterable>)`, which binds the pattern afresh for each element
+    ForIn { pattern: Pattern, iterable: Expression, body: Box<Statement>, label: Option<String> },
+    Break { label: Option<String> },
+    Continue { label: Option<String> },
+    FunctionDeclaration(Rc<FunctionDefinition>),
+    ReturnStatement(Option<Expression>),
+    ClassDeclaration { name: String, superclass: Option<Expression>, methods: Vec<Rc<FunctionDefinition>> },
+    EnumDeclaration(Rc<LoxEnum>),
+    Throw(Expression),
+    // at least one of catch and finally is present; catch holds the name the error is bound to
+    Try { body: Vec<Statement>, catch: Option<(String, Vec<Statement>)>, finally: Option<Vec<Statement>> },
+    // `import "<path>" as <name>;`
+    Import { path: String, name: String },
+    // a top-level var, fun, class or enum declaration that is visible to importers
+    Export(Box<Statement>),
+    // the subject is evaluated once, then the body of the first arm that matches it is run
+    Match { subject: Expression, arms: Vec<MatchArm> },
+}
+
+/// The names bound by a `var` declaration
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Name(String),
+    // `[a, b, ...rest]`
+    List { elements: Vec<Pattern>, rest: Option<String> },
+    // `{name, age: years}`, binding the value of each key to a pattern
+    Map { entries: Vec<(String, Pattern)> },
+}
+
+impl Pattern {
+    /// Every name the pattern binds, in order
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Pattern::Name(name) => vec![name],
+            Pattern::List { elements, rest } => elements
+                .iter()
+                .flat_map(Pattern::names)
+                .chain(rest.as_deref())
+                .collect(),
+            Pattern::Map { entries } => entries
+                .iter()
+                .flat_map(|(_, pattern)| pattern.names())
+                .collect(),
+        }
+    }
+}
+
+/// One `case <pattern> if <guard> => <body>` arm of a match statement
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub guard: Option<Expression>,
+    pub body: Box<Statement>,
+}
+
+/// The shape a value has to have for a match arm to run
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    // `_`, which matches anything without binding it
+    Wildcard,
+    // matches anything and binds it to the name
+    Binding(String),
+    // matches values equal to the literal
+    Literal(Literal),
+    // matches lists with exactly as many elements as there are patterns,
+    // or at least as many when there is a `...rest` pattern
+    List { elements: Vec<MatchPattern>, rest: Option<String> },
+    // `Shape.Circle(r)` matches values of the named variant, destructuring their fields;
+    // without the parentheses it matches the variant whatever its fields
+    Variant { enumeration: String, variant: String, fields: Option<Vec<MatchPattern>> },
+}
+
+impl MatchPattern {
+    /// Whether the pattern matches every value
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, MatchPattern::Wildcard | MatchPattern::Binding(_))
+    }
+
+    /// Every name the pattern binds, in order
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            MatchPattern::Binding(name) => vec![name],
+            MatchPattern::List { elements, rest } => elements
+                .iter()
+                .flat_map(MatchPattern::names)
+                // `..._` collects the rest of a list without binding it
+                .chain(rest.as_deref().filter(|rest| *rest != "_"))
+                .collect(),
+            MatchPattern::Variant { fields, .. } => fields
+                .iter()
+                .flatten()
+                .flat_map(MatchPattern::names)
+                .collect(),
+            MatchPattern::Wildcard | MatchPattern::Literal(_) => vec![],
+        }
+    }
+}
+
+/// The parts of a function shared by `fun` declarations, class methods and lambdas
+#[derive(Debug, Clone)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub params: Vec<Parameter>,
+    // the name of the `...rest` parameter collecting extra positional arguments
+    pub rest: Option<String>,
+    pub body: Vec<Statement>,
+    // declared with `fun*`, so calling it creates a generator instead of running the body
+    pub is_generator: bool,
+    pub return_type: Option<Type>,
+}
+
+/// A named parameter, with the expression that gives its value when no argument is passed
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub default: Option<Expression>,
+    pub annotation: Option<Type>,
 }
 
 pub type Expression = MetaContainer<ExpressionItem>;
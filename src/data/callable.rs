@@ -0,0 +1,138 @@
+use super::ast::FunctionDefinition;
+use super::literals::Literal;
+use crate::environment::Environment;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A user-defined function, created by evaluating a `fun` declaration
+/// or a method in a class declaration.
+/// The function closes over the environment it was declared in
+pub struct LoxFunction {
+    pub definition: Rc<FunctionDefinition>,
+    pub closure: Environment,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    /// Create a copy of this method with `this` bound to the given instance
+    pub fn bind(&self, instance: Rc<LoxInstance>) -> LoxFunction {
+        let mut closure = self.closure.clone();
+        closure.fork();
+        closure.define(String::from("this"), Literal::Instance(instance));
+        LoxFunction {
+            definition: self.definition.clone(),
+            closure,
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+/// functions are only ever equal to themselves
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// The signature of a Rust function exposed to Lox
+pub type NativeFn = dyn Fn(Vec<Literal>) -> Result<Literal, String>;
+
+/// A function implemented in Rust, registered with `Interpreter::define_native`
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: Box<NativeFn>,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    /// Look up a method on this class, falling back to its superclasses
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.methods.get(name).cloned().or_else(|| {
+            self.superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name))
+        })
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: RefCell<HashMap<String, Rc<Literal>>>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        LoxInstance {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Get a field, or failing that a method bound to this instance
+    pub fn get(instance: &Rc<LoxInstance>, name: &str) -> Option<Rc<Literal>> {
+        if let Some(value) = instance.fields.borrow().get(name) {
+            return Some(value.clone());
+        }
+        instance.class.find_method(name).map(|method| {
+            Rc::new(Literal::Function(Rc::new(method.bind(instance.clone()))))
+        })
+    }
+
+    pub fn set(&self, name: String, value: Rc<Literal>) {
+        self.fields.borrow_mut().insert(name, value);
+    }
+}
+
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
+
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -0,0 +1,71 @@
+use super::literals::Literal;
+use std::fmt;
+use std::rc::Rc;
+
+/// An `enum` declaration: a name and the variants its values can take
+pub struct LoxEnum {
+    pub name: String,
+    pub variants: Vec<Variant>,
+}
+
+/// One variant of an enum, with the names of the fields its values carry
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl LoxEnum {
+    /// The index of the variant with the given name
+    pub fn tag(&self, name: &str) -> Option<usize> {
+        self.variants.iter().position(|variant| variant.name == name)
+    }
+}
+
+impl fmt::Debug for LoxEnum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<enum {}>", self.name)
+    }
+}
+
+/// enums are only ever equal to themselves
+impl PartialEq for LoxEnum {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A value of an enum: the variant it is, and the values of that variant's fields
+#[derive(Debug)]
+pub struct EnumValue {
+    pub enumeration: Rc<LoxEnum>,
+    pub tag: usize,
+    pub payload: Vec<Literal>,
+}
+
+impl EnumValue {
+    pub fn variant(&self) -> &Variant {
+        &self.enumeration.variants[self.tag]
+    }
+
+    /// Look up a field by name; `tag` is the name of the variant
+    pub fn get(&self, name: &str) -> Option<Literal> {
+        if name == "tag" {
+            return Some(Literal::StringT(self.variant().name.clone()));
+        }
+        self.variant()
+            .fields
+            .iter()
+            .position(|field| field == name)
+            .map(|i| self.payload[i].clone())
+    }
+}
+
+/// values are equal when they are the same variant of the same enum with equal fields
+impl PartialEq for EnumValue {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.enumeration, &other.enumeration)
+            && self.tag == other.tag
+            && self.payload == other.payload
+    }
+}
@@ -1,3 +1,5 @@
+use super::literals::Literal;
+use std::fmt;
 use std::io;
 
 #[derive(Debug)]
@@ -13,3 +15,55 @@ pub enum LoxError {
     ScannerError(ErrorData),
 }
 
+
+/// A runtime error as a Lox value, produced when a `catch` block catches an error
+/// raised by the interpreter or created with the `error` native
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: String,
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+impl RuntimeError {
+    /// Parse the message of an interpreter error, formatted as `Line <n>: <kind>: <message>`,
+    /// where the line and kind are both optional
+    pub fn from_message(error: &str) -> Self {
+        let (line, rest) = match error
+            .strip_prefix("Line ")
+            .and_then(|rest| rest.split_once(": "))
+        {
+            Some((line, rest)) if line.parse::<usize>().is_ok() => (line.parse().ok(), rest),
+            _ => (None, error),
+        };
+        let (kind, message) = match rest.split_once(": ") {
+            Some((kind, message)) if kind.ends_with(" Error") => (kind, message),
+            _ => ("Runtime Error", rest),
+        };
+        RuntimeError {
+            kind: kind.to_owned(),
+            message: message.to_owned(),
+            line,
+        }
+    }
+
+    /// Look up one of the properties of the error: `message`, `kind` or `line`
+    pub fn get(&self, name: &str) -> Option<Literal> {
+        match name {
+            "message" => Some(Literal::StringT(self.message.clone())),
+            "kind" => Some(Literal::StringT(self.kind.clone())),
+            "line" => Some(
+                self.line
+                    .map(|line| Literal::Integer(line as i64))
+                    .unwrap_or(Literal::Nil),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
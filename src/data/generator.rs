@@ -0,0 +1,118 @@
+use super::ast::FunctionDefinition;
+use super::literals::Literal;
+use crate::interpeter::Interrupt;
+use corosensei::stack::DefaultStack;
+use corosensei::{Coroutine, Yielder};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// The object returned by calling a `fun*` function.
+/// Its body runs a piece at a time, each time `next` or `send` is called,
+/// suspending at every `yield` until the body finishes
+pub struct LoxGenerator {
+    pub definition: Rc<FunctionDefinition>,
+    pub(crate) state: RefCell<GeneratorState>,
+}
+
+impl LoxGenerator {
+    /// A generator that has not started, which runs `body` when it is first resumed
+    pub(crate) fn new(definition: Rc<FunctionDefinition>, body: GeneratorBody) -> Self {
+        LoxGenerator {
+            definition,
+            state: RefCell::new(GeneratorState {
+                status: GeneratorStatus::Created,
+                body: Some(body),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().status == GeneratorStatus::Finished
+    }
+}
+
+impl fmt::Debug for LoxGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<generator {}>", self.name())
+    }
+}
+
+/// generators are only ever equal to themselves
+impl PartialEq for LoxGenerator {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeneratorStatus {
+    Created,
+    Suspended,
+    // the body is being run; resuming it again from inside is an error
+    Running,
+    Finished,
+}
+
+/// Everything needed to carry on running a generator's body from where it stopped
+pub(crate) struct GeneratorState {
+    pub status: GeneratorStatus,
+    // the body, running on its own stack; taken out while it runs, and dropped once it finishes
+    pub body: Option<GeneratorBody>,
+}
+
+/// What a suspended generator is resumed with, which becomes the result of the `yield` it stopped at
+pub(crate) enum Resumption {
+    // carry on, with the value sent (nil for `next`) as the value of the yield
+    Send(Literal),
+    // return from the yield, running any pending `finally` blocks
+    Close,
+}
+
+/// A generator's body as a coroutine, which yields the values of its `yield` expressions
+/// and finishes with the value it returns
+pub(crate) type GeneratorBody = Coroutine<Resumption, Literal, Result<Literal, Interrupt>, DefaultStack>;
+
+/// The handle a generator's body suspends itself with
+pub(crate) type GeneratorYielder = Yielder<Resumption, Literal>;
+
+/// The methods of a generator, which are called like the methods of an instance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeneratorMethod {
+    // resume with nil as the value of the yield
+    Next,
+    // resume with the argument as the value of the yield
+    Send,
+    // finish early, running any pending `finally` blocks
+    Close,
+}
+
+impl GeneratorMethod {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "next" => Some(GeneratorMethod::Next),
+            "send" => Some(GeneratorMethod::Send),
+            "close" => Some(GeneratorMethod::Close),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GeneratorMethod::Next => "next",
+            GeneratorMethod::Send => "send",
+            GeneratorMethod::Close => "close",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            GeneratorMethod::Send => 1,
+            GeneratorMethod::Next | GeneratorMethod::Close => 0,
+        }
+    }
+}
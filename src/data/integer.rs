@@ -0,0 +1,180 @@
+use super::literals::Literal;
+use super::tokens::Token;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// The most bits the result of `**` or `<<` may have, so that a typo like `2 ** 4000000000`
+/// is an error rather than an attempt to allocate gigabytes
+const MAX_BITS: u64 = 1 << 22;
+
+/// Give the literal for an integer: `Literal::Integer` when it fits in an i64,
+/// and `Literal::BigInteger` only when it doesn't, so every integer has exactly one representation
+pub fn normalize(value: BigInt) -> Literal {
+    match value.to_i64() {
+        Some(i) => Literal::Integer(i),
+        None => Literal::BigInteger(Rc::new(value)),
+    }
+}
+
+/// The value of either kind of integer literal
+pub fn to_big(literal: &Literal) -> Option<BigInt> {
+    match literal {
+        Literal::Integer(i) => Some(BigInt::from(*i)),
+        Literal::BigInteger(i) => Some(i.as_ref().clone()),
+        _ => None,
+    }
+}
+
+pub fn is_integer(literal: &Literal) -> bool {
+    matches!(literal, Literal::Integer(_) | Literal::BigInteger(_))
+}
+
+/// Apply a binary operator to two integers, promoting to a big integer when the result
+/// overflows an i64. Gives None when either operand isn't an integer, or the operator is
+/// not one that integers handle themselves, such as `==` or `**` with a negative exponent
+pub fn binary_operation(
+    operator: &Token,
+    left: &Literal,
+    right: &Literal,
+) -> Option<Result<Literal, String>> {
+    if let (Literal::Integer(left), Literal::Integer(right)) = (left, right) {
+        if let Some(result) = small_operation(operator, *left, *right) {
+            return Some(result);
+        }
+    }
+    big_operation(operator, to_big(left)?, to_big(right)?)
+}
+
+/// Operations on two i64s, or None when the result doesn't fit in an i64
+fn small_operation(operator: &Token, left: i64, right: i64) -> Option<Result<Literal, String>> {
+    let integer = |value: Option<i64>| value.map(|value| Ok(Literal::Integer(value)));
+    let boolean = |value: bool| Some(Ok(if value { Literal::True } else { Literal::False }));
+    match operator {
+        Token::Star => integer(left.checked_mul(right)),
+        Token::Minus => integer(left.checked_sub(right)),
+        Token::Plus => integer(left.checked_add(right)),
+        Token::Slash => Some(Ok(Literal::Number(left as f64 / right as f64))),
+        Token::Greater => boolean(left > right),
+        Token::GreaterEqual => boolean(left >= right),
+        Token::Lesser => boolean(left < right),
+        Token::LesserEqual => boolean(left <= right),
+        Token::Percent | Token::TildeSlash if right == 0 => Some(Err(division_by_zero(operator))),
+        // checked_rem and checked_div only fail on i64::MIN and -1
+        Token::Percent => integer(left.checked_rem(right).map(|_| left.mod_floor(&right))),
+        Token::TildeSlash => integer(
+            left.checked_div(right)
+                .map(|_| Integer::div_floor(&left, &right)),
+        ),
+        Token::StarStar if right >= 0 => integer(
+            u32::try_from(right)
+                .ok()
+                .and_then(|right| left.checked_pow(right)),
+        ),
+        Token::Ampersand => integer(Some(left & right)),
+        Token::Pipe => integer(Some(left | right)),
+        Token::Caret => integer(Some(left ^ right)),
+        // shifting left can overflow, which shows up as not being able to shift back
+        Token::LesserLesser if (0..64).contains(&right) && (left << right) >> right == left => {
+            integer(Some(left << right))
+        }
+        // anything shifted right by 63 or more is all sign bits
+        Token::GreaterGreater if right >= 0 => integer(Some(left >> right.min(63))),
+        _ => None,
+    }
+}
+
+fn big_operation(operator: &Token, left: BigInt, right: BigInt) -> Option<Result<Literal, String>> {
+    let boolean = |value: bool| Some(Ok(if value { Literal::True } else { Literal::False }));
+    Some(Ok(match operator {
+        Token::Star => normalize(left * right),
+        Token::Minus => normalize(left - right),
+        Token::Plus => normalize(left + right),
+        Token::Slash => Literal::Number(to_f64(&left) / to_f64(&right)),
+        Token::Greater => return boolean(left > right),
+        Token::GreaterEqual => return boolean(left >= right),
+        Token::Lesser => return boolean(left < right),
+        Token::LesserEqual => return boolean(left <= right),
+        Token::Percent | Token::TildeSlash if right.is_zero() => {
+            return Some(Err(division_by_zero(operator)))
+        }
+        Token::Percent => normalize(left.mod_floor(&right)),
+        Token::TildeSlash => normalize(left.div_floor(&right)),
+        // a negative exponent gives a fraction, which is left to floating point
+        Token::StarStar if right.is_negative() => return None,
+        // 0, 1 and -1 stay that small whatever the exponent
+        Token::StarStar if left.bits() <= 1 => match left.to_i64() {
+            Some(0) if !right.is_zero() => Literal::Integer(0),
+            Some(-1) if right.is_odd() => Literal::Integer(-1),
+            _ => Literal::Integer(1),
+        },
+        // a base of `bits` bits raised to `exponent` has at most bits * exponent bits
+        Token::StarStar => match right.to_u64() {
+            Some(exponent) if left.bits().saturating_mul(exponent) <= MAX_BITS => {
+                normalize(left.pow(exponent as u32))
+            }
+            _ => return Some(Err(too_large(operator))),
+        },
+        Token::Ampersand => normalize(left & right),
+        Token::Pipe => normalize(left | right),
+        Token::Caret => normalize(left ^ right),
+        Token::LesserLesser | Token::GreaterGreater if right.is_negative() => {
+            return Some(Err(format!("Cannot shift by {}; shifts must not be negative", right)))
+        }
+        // shifting every bit out leaves only the sign
+        Token::GreaterGreater => match right.to_u64() {
+            Some(shift) if shift < left.bits() => normalize(left >> shift),
+            _ => Literal::Integer(if left.is_negative() { -1 } else { 0 }),
+        },
+        Token::LesserLesser if left.is_zero() => Literal::Integer(0),
+        Token::LesserLesser => match right.to_u64() {
+            Some(shift) if left.bits().saturating_add(shift) <= MAX_BITS => normalize(left << shift),
+            _ => return Some(Err(too_large(operator))),
+        },
+        _ => return None,
+    }))
+}
+
+/// The nearest float to an integer, which is infinite for integers too large for an f64
+pub fn to_f64(value: &BigInt) -> f64 {
+    value.to_f64().unwrap_or(if value.is_negative() {
+        f64::NEG_INFINITY
+    } else {
+        f64::INFINITY
+    })
+}
+
+/// Negate either kind of integer literal
+pub fn negate(literal: &Literal) -> Option<Literal> {
+    match literal {
+        Literal::Integer(i) => Some(
+            i.checked_neg()
+                .map(Literal::Integer)
+                .unwrap_or_else(|| normalize(-BigInt::from(*i))),
+        ),
+        Literal::BigInteger(i) => Some(normalize(-i.as_ref())),
+        _ => None,
+    }
+}
+
+/// Flip the bits of either kind of integer literal, as two's complement
+pub fn complement(literal: &Literal) -> Option<Literal> {
+    match literal {
+        Literal::Integer(i) => Some(Literal::Integer(!i)),
+        Literal::BigInteger(i) => Some(normalize(!i.as_ref())),
+        _ => None,
+    }
+}
+
+fn division_by_zero(operator: &Token) -> String {
+    format!("Division by zero in {}", operator)
+}
+
+fn too_large(operator: &Token) -> String {
+    format!(
+        "The result of {} is too large; integers are limited to {} bits",
+        operator, MAX_BITS
+    )
+}
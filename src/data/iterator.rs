@@ -0,0 +1,92 @@
+use super::generator::LoxGenerator;
+use super::literals::Literal;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The state of a `for (x in iterable)` loop over a value.
+/// Values are produced one at a time, so iterating a range never builds a list
+pub enum LoxIterator {
+    // the length is checked on every step, so elements pushed during the loop are visited
+    List {
+        elements: Rc<RefCell<Vec<Literal>>>,
+        index: usize,
+    },
+    // byte offset of the next character
+    Chars { string: String, offset: usize },
+    // the keys of a map, as they were when the loop started
+    Keys(std::vec::IntoIter<Literal>),
+    // None once an inclusive range has yielded i64::MAX
+    Range {
+        next: Option<i64>,
+        end: i64,
+        inclusive: bool,
+    },
+    // resumed by the interpreter, since running a generator means running Lox code
+    Generator(Rc<LoxGenerator>),
+}
+
+impl LoxIterator {
+    pub fn new(iterable: &Literal) -> Result<Self, String> {
+        match iterable {
+            Literal::List(elements) => Ok(LoxIterator::List {
+                elements: elements.clone(),
+                index: 0,
+            }),
+            Literal::StringT(string) => Ok(LoxIterator::Chars {
+                string: string.clone(),
+                offset: 0,
+            }),
+            Literal::Map(entries) => Ok(LoxIterator::Keys(
+                entries
+                    .borrow()
+                    .keys()
+                    .map(|key| key.to_literal())
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            Literal::Range {
+                start,
+                end,
+                inclusive,
+            } => Ok(LoxIterator::Range {
+                next: Some(*start),
+                end: *end,
+                inclusive: *inclusive,
+            }),
+            Literal::Generator(generator) => Ok(LoxIterator::Generator(generator.clone())),
+            v => Err(format!("Type Error: {} is not iterable", v)),
+        }
+    }
+
+    /// The next value of any iterator but a generator's, or None once there are no more
+    pub fn advance(&mut self) -> Option<Literal> {
+        match self {
+            LoxIterator::List { elements, index } => {
+                let element = elements.borrow().get(*index).cloned()?;
+                *index += 1;
+                Some(element)
+            }
+            LoxIterator::Chars { string, offset } => {
+                let c = string[*offset..].chars().next()?;
+                *offset += c.len_utf8();
+                Some(Literal::StringT(c.to_string()))
+            }
+            LoxIterator::Keys(keys) => keys.next(),
+            LoxIterator::Range {
+                next,
+                end,
+                inclusive,
+            } => {
+                let current = (*next)?;
+                if current > *end || (current == *end && !*inclusive) {
+                    return None;
+                }
+                *next = current.checked_add(1);
+                Some(Literal::Integer(current))
+            }
+            LoxIterator::Generator(_) => {
+                unreachable!("generators are resumed through the interpreter")
+            }
+        }
+    }
+}
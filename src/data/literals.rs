@@ -1,14 +1,180 @@
+use super::callable::{LoxClass, LoxFunction, LoxInstance, NativeFunction};
+use super::enums::{EnumValue, LoxEnum};
+use super::errors::RuntimeError;
+use super::generator::{GeneratorMethod, LoxGenerator};
+use super::module::LoxModule;
+use num_bigint::BigInt;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
+use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Literal {
     Identifier(String),
     StringT(String),
     Integer(i64),
+    // an integer too large for an i64; integers that fit are always `Integer`
+    BigInteger(Rc<BigInt>),
     Number(f64),
     True,
     False,
-    Nil
+    Nil,
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<BTreeMap<MapKey, Literal>>>),
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<LoxInstance>),
+    Error(Rc<RuntimeError>),
+    Module(Rc<LoxModule>),
+    Generator(Rc<LoxGenerator>),
+    // a method of a generator, looked up with `generator.next` etc
+    GeneratorMethod(Rc<LoxGenerator>, GeneratorMethod),
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
+    Enum(Rc<LoxEnum>),
+    // a variant with fields, looked up with `Shape.Circle`, which is called to make a value
+    VariantConstructor(Rc<LoxEnum>, usize),
+    Variant(Rc<EnumValue>),
+}
+
+thread_local! {
+    // the pairs of lists or maps being compared, and the lists or maps being displayed,
+    // so that lists and maps that contain themselves can be compared and displayed
+    static COMPARING: RefCell<HashSet<(usize, usize)>> = RefCell::new(HashSet::new());
+    static DISPLAYING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+/// Lists and maps are equal when they hold equal values, and are compared that way
+/// rather than by reference; every other value compares the way its Rust type does
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        use Literal::*;
+        match (self, other) {
+            (List(left), List(right)) => same_contents(left, right),
+            (Map(left), Map(right)) => same_contents(left, right),
+            (Identifier(left), Identifier(right)) | (StringT(left), StringT(right)) => left == right,
+            (Integer(left), Integer(right)) => left == right,
+            (BigInteger(left), BigInteger(right)) => left == right,
+            (Number(left), Number(right)) => left == right,
+            (True, True) | (False, False) | (Nil, Nil) => true,
+            (Function(left), Function(right)) => left == right,
+            (NativeFunction(left), NativeFunction(right)) => left == right,
+            (Class(left), Class(right)) => left == right,
+            (Instance(left), Instance(right)) => left == right,
+            (Error(left), Error(right)) => left == right,
+            (Module(left), Module(right)) => left == right,
+            (Generator(left), Generator(right)) => left == right,
+            (GeneratorMethod(left, left_method), GeneratorMethod(right, right_method)) => {
+                left == right && left_method == right_method
+            }
+            (
+                Range { start, end, inclusive },
+                Range { start: other_start, end: other_end, inclusive: other_inclusive },
+            ) => start == other_start && end == other_end && inclusive == other_inclusive,
+            (Enum(left), Enum(right)) => left == right,
+            (VariantConstructor(left, left_tag), VariantConstructor(right, right_tag)) => {
+                left == right && left_tag == right_tag
+            }
+            (Variant(left), Variant(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+/// Whether two lists or maps hold equal values. A pair that is reached again while it is
+/// still being compared is taken to be equal, which is what makes self-containing lists comparable
+fn same_contents<T: PartialEq>(left: &Rc<RefCell<T>>, right: &Rc<RefCell<T>>) -> bool {
+    if Rc::ptr_eq(left, right) {
+        return true;
+    }
+    let pair = (Rc::as_ptr(left) as usize, Rc::as_ptr(right) as usize);
+    if !COMPARING.with(|comparing| comparing.borrow_mut().insert(pair)) {
+        return true;
+    }
+    let equal = *left.borrow() == *right.borrow();
+    COMPARING.with(|comparing| comparing.borrow_mut().remove(&pair));
+    equal
+}
+
+/// Display a list or map, or `placeholder` if it is already being displayed further out
+fn display_once<T>(container: &Rc<RefCell<T>>, placeholder: &str, display: impl FnOnce(&T) -> String) -> String {
+    let address = Rc::as_ptr(container) as usize;
+    if !DISPLAYING.with(|displaying| displaying.borrow_mut().insert(address)) {
+        return placeholder.to_owned();
+    }
+    let text = display(&container.borrow());
+    DISPLAYING.with(|displaying| displaying.borrow_mut().remove(&address));
+    text
+}
+
+/// The subset of literals that can be used as map keys.
+/// Keys are equal exactly when the literals they came from are `==`,
+/// so `1` and `1.0` are different keys, but `0.0` and `-0.0` are the same key
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MapKey {
+    Nil,
+    False,
+    True,
+    Integer(i64),
+    BigInteger(BigInt),
+    // the bits of the float, transformed so that integer ordering matches float ordering
+    Number(u64),
+    StringT(String),
+}
+
+impl MapKey {
+    pub fn from_literal(literal: &Literal) -> Result<MapKey, String> {
+        match literal {
+            Literal::Nil => Ok(MapKey::Nil),
+            Literal::False => Ok(MapKey::False),
+            Literal::True => Ok(MapKey::True),
+            Literal::Integer(i) => Ok(MapKey::Integer(*i)),
+            Literal::BigInteger(i) => Ok(MapKey::BigInteger(i.as_ref().clone())),
+            Literal::Number(n) if n.is_nan() => {
+                Err(String::from("Type Error: NaN cannot be used as a map key"))
+            }
+            Literal::Number(n) => {
+                // adding 0.0 turns -0.0 into 0.0
+                let bits = (n + 0.0).to_bits();
+                Ok(MapKey::Number(if bits >> 63 == 1 {
+                    !bits
+                } else {
+                    bits | (1 << 63)
+                }))
+            }
+            Literal::StringT(s) => Ok(MapKey::StringT(s.clone())),
+            v => Err(format!("Type Error: {} cannot be used as a map key", v)),
+        }
+    }
+
+    pub fn to_literal(&self) -> Literal {
+        match self {
+            MapKey::Nil => Literal::Nil,
+            MapKey::False => Literal::False,
+            MapKey::True => Literal::True,
+            MapKey::Integer(i) => Literal::Integer(*i),
+            MapKey::BigInteger(i) => Literal::BigInteger(Rc::new(i.clone())),
+            MapKey::Number(bits) => Literal::Number(f64::from_bits(if bits >> 63 == 1 {
+                bits & !(1 << 63)
+            } else {
+                !bits
+            })),
+            MapKey::StringT(s) => Literal::StringT(s.clone()),
+        }
+    }
+}
+
+/// Format a literal as it appears inside a list or map, where strings are quoted
+fn repr(literal: &Literal) -> String {
+    match literal {
+        Literal::StringT(s) => format!("{:?}", s),
+        literal => format!("{}", literal),
+    }
 }
 
 impl fmt::Display for Literal {
@@ -19,12 +185,54 @@ impl fmt::Display for Literal {
             "{}",
             match self {
                 Identifier(s) => format!("id:{}", s),
-                StringT(s) => format!("{}", s),
+                StringT(s) => s.to_string(),
                 Integer(i) => format!("{}", i),
+                BigInteger(i) => format!("{}", i),
                 Number(f) => format!("{}", f),
                 True => "true".to_owned(),
                 False => "false".to_owned(),
-                Nil => "nil".to_owned()
+                Nil => "nil".to_owned(),
+                List(elements) => display_once(elements, "[...]", |elements| {
+                    format!("[{}]", elements.iter().map(repr).collect::<Vec<_>>().join(", "))
+                }),
+                Map(entries) => display_once(entries, "{...}", |entries| {
+                    format!(
+                        "{{{}}}",
+                        entries
+                            .iter()
+                            .map(|(key, value)| format!("{}: {}", repr(&key.to_literal()), repr(value)))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                }),
+                Function(function) => format!("<fn {}>", function.name()),
+                NativeFunction(function) => format!("<native fn {}>", function.name),
+                Class(class) => class.name.clone(),
+                Instance(instance) => format!("{} instance", instance.class.name),
+                Error(error) => format!("<{}>", error),
+                Module(module) => format!("<module {}>", module.path.display()),
+                Generator(generator) => format!("<generator {}>", generator.name()),
+                GeneratorMethod(generator, method) =>
+                    format!("<generator method {} of {}>", method.name(), generator.name()),
+                Range {
+                    start,
+                    end,
+                    inclusive,
+                } => format!("{}{}{}", start, if *inclusive { "..=" } else { ".." }, end),
+                Enum(enumeration) => format!("<enum {}>", enumeration.name),
+                VariantConstructor(enumeration, tag) => format!(
+                    "<variant {}.{}>",
+                    enumeration.name, enumeration.variants[*tag].name
+                ),
+                Variant(value) if value.payload.is_empty() => {
+                    format!("{}.{}", value.enumeration.name, value.variant().name)
+                }
+                Variant(value) => format!(
+                    "{}.{}({})",
+                    value.enumeration.name,
+                    value.variant().name,
+                    value.payload.iter().map(repr).collect::<Vec<_>>().join(", ")
+                ),
             }
         )
     }
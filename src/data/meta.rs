@@ -13,9 +13,13 @@ impl<T> MetaContainer<T> {
         self.line
     }
 
-    pub fn item<'a>(&'a self)-> &'a T {
+    pub fn item(&self)-> &T {
         &self.item
     }
+
+    pub fn item_mut(&mut self)-> &mut T {
+        &mut self.item
+    }
 }
 
 impl<T> MetaContainer<T> where T: Copy {
@@ -1,5 +1,12 @@
 pub mod ast;
+pub mod callable;
+pub mod enums;
 pub mod errors;
+pub mod generator;
+pub mod integer;
+pub mod iterator;
 pub mod literals;
+pub mod module;
 pub mod tokens;
+pub mod types;
 pub mod meta;
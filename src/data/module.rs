@@ -0,0 +1,38 @@
+use super::literals::Literal;
+use crate::environment::Environment;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// The namespace produced by importing a file.
+/// Only the names the file declared with `export` can be read through it
+pub struct LoxModule {
+    pub path: PathBuf,
+    pub globals: Environment,
+    pub exports: HashSet<String>,
+}
+
+impl LoxModule {
+    /// Get the current value of an exported name
+    pub fn get(&self, name: &str) -> Option<Rc<Literal>> {
+        if self.exports.contains(name) {
+            self.globals.get(name)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for LoxModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.path.display())
+    }
+}
+
+/// modules are only ever equal to themselves
+impl PartialEq for LoxModule {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
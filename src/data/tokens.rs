@@ -9,6 +9,8 @@ pub enum Token {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -18,6 +20,11 @@ pub enum Token {
     Star,
     Question,
     Colon,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two chars
     Bang,
@@ -28,43 +35,88 @@ pub enum Token {
     GreaterEqual,
     Lesser,
     LesserEqual,
+    StarStar,
+    TildeSlash,
+    LesserLesser,
+    GreaterGreater,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PlusPlus,
+    MinusMinus,
+    Arrow,
+    Ellipsis,
+    DotDot,
+    DotDotEqual,
 
     // Literals
     Literal(Literal),
+    InterpolatedString(Vec<StringPart>),
 
     // Keywords
     And,
+    As,
+    Break,
+    Case,
+    Catch,
     Class,
+    Const,
+    Continue,
     Else,
+    Enum,
+    Export,
+    Finally,
     Fun,
     For,
     If,
+    Import,
+    In,
+    Match,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
+    Try,
     Var,
     While,
+    Yield,
 
     EOF,
 }
 
+/// A piece of a string literal containing `${...}` interpolations
+#[derive(Debug, Clone)]
+pub enum StringPart {
+    Literal(String),
+    Interpolation(Vec<TokenMeta>),
+}
+
 impl Token {
     pub fn is_value(&self) -> bool {
         use Token::*;
+        matches!(self, Literal(_))
+    }
+    /// The token as it was written in the source, which is how error messages quote it
+    pub fn lexeme(&self) -> String {
         match self {
-            Literal(_) => true,
-            _ => false,
+            Token::Literal(Literal::Identifier(name)) => name.clone(),
+            Token::Literal(Literal::StringT(string)) => format!("\"{}\"", string),
+            token => token.to_string(),
         }
     }
     pub fn is_operator(&self) -> bool {
         use Token::*;
-        match self {
-            Bang | BangEqual | Equal | EqualEqual | Greater | GreaterEqual => true,
-            Lesser | LesserEqual | Star | Slash | Plus | Minus => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            BangEqual | Equal | EqualEqual | Greater | GreaterEqual
+                | Lesser | LesserEqual | Star | Slash | Plus | Minus
+                | Percent | Ampersand | Pipe | Caret | StarStar | TildeSlash
+                | LesserLesser | GreaterGreater | PlusEqual | MinusEqual
+                | StarEqual | SlashEqual
+        )
     }
 }
 
@@ -80,6 +132,8 @@ impl fmt::Display for Token {
                 RightParen => ")".to_owned(),
                 LeftBrace => "{".to_owned(),
                 RightBrace => "}".to_owned(),
+                LeftBracket => "[".to_owned(),
+                RightBracket => "]".to_owned(),
                 Comma => ",".to_owned(),
                 Dot => ".".to_owned(),
                 Minus => "-".to_owned(),
@@ -89,6 +143,11 @@ impl fmt::Display for Token {
                 Star => "*".to_owned(),
                 Question => "?".to_owned(),
                 Colon => ":".to_owned(),
+                Percent => "%".to_owned(),
+                Ampersand => "&".to_owned(),
+                Pipe => "|".to_owned(),
+                Caret => "^".to_owned(),
+                Tilde => "~".to_owned(),
 
                 // One or two chars
                 Bang => "!".to_owned(),
@@ -99,23 +158,59 @@ impl fmt::Display for Token {
                 GreaterEqual => ">=".to_owned(),
                 Lesser => "<".to_owned(),
                 LesserEqual => "<=".to_owned(),
+                StarStar => "**".to_owned(),
+                TildeSlash => "~/".to_owned(),
+                LesserLesser => "<<".to_owned(),
+                GreaterGreater => ">>".to_owned(),
+                PlusEqual => "+=".to_owned(),
+                MinusEqual => "-=".to_owned(),
+                StarEqual => "*=".to_owned(),
+                SlashEqual => "/=".to_owned(),
+                PlusPlus => "++".to_owned(),
+                MinusMinus => "--".to_owned(),
+                Arrow => "=>".to_owned(),
+                Ellipsis => "...".to_owned(),
+                DotDot => "..".to_owned(),
+                DotDotEqual => "..=".to_owned(),
 
                 Literal(l) => format!("{}", l),
+                InterpolatedString(parts) => parts
+                    .iter()
+                    .map(|part| match part {
+                        StringPart::Literal(s) => s.clone(),
+                        StringPart::Interpolation(_) => "${...}".to_owned(),
+                    })
+                    .collect(),
 
                 // Keywords
                 And => "and".to_owned(),
+                As => "as".to_owned(),
+                Break => "break".to_owned(),
+                Case => "case".to_owned(),
+                Catch => "catch".to_owned(),
                 Class => "class".to_owned(),
+                Const => "const".to_owned(),
+                Continue => "continue".to_owned(),
                 Else => "else".to_owned(),
+                Enum => "enum".to_owned(),
+                Export => "export".to_owned(),
+                Finally => "finally".to_owned(),
                 Fun => "fun".to_owned(),
                 For => "for".to_owned(),
                 If => "if".to_owned(),
+                Import => "import".to_owned(),
+                In => "in".to_owned(),
+                Match => "match".to_owned(),
                 Or => "or".to_owned(),
                 Print => "print".to_owned(),
                 Return => "return".to_owned(),
                 Super => "super".to_owned(),
                 This => "this".to_owned(),
+                Throw => "throw".to_owned(),
+                Try => "try".to_owned(),
                 Var => "var".to_owned(),
                 While => "while".to_owned(),
+                Yield => "yield".to_owned(),
 
                 EOF => "EOF".to_owned(),
             }
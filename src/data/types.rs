@@ -0,0 +1,59 @@
+use super::literals::Literal;
+use std::fmt;
+
+/// The types that can be written in an annotation, as in `var x: Int = 3;`.
+/// Anything without an annotation is `Any`, which the checker never complains about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Any,
+    Int,
+    Num,
+    Str,
+    Bool,
+    Nil,
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Any" => Some(Type::Any),
+            "Int" => Some(Type::Int),
+            "Num" => Some(Type::Num),
+            "Str" => Some(Type::Str),
+            "Bool" => Some(Type::Bool),
+            "Nil" => Some(Type::Nil),
+            _ => None,
+        }
+    }
+
+    /// The type of a literal value; values of the kinds without a type name are `Any`
+    pub fn of(literal: &Literal) -> Self {
+        match literal {
+            Literal::Integer(_) | Literal::BigInteger(_) => Type::Int,
+            Literal::Number(_) => Type::Num,
+            Literal::StringT(_) => Type::Str,
+            Literal::True | Literal::False => Type::Bool,
+            Literal::Nil => Type::Nil,
+            _ => Type::Any,
+        }
+    }
+
+    /// Whether a value of this type can be used where `expected` is required.
+    /// `Any` fits everywhere and everything fits `Any`, and integers are numbers
+    pub fn fits(self, expected: Type) -> bool {
+        self == expected
+            || self == Type::Any
+            || expected == Type::Any
+            || (self == Type::Int && expected == Type::Num)
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Type::Int | Type::Num)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::data::literals::Literal;
+
+/// Which language the interpreter accepts.
+/// The extended dialect is the default; the book dialect follows the reference
+/// implementation from Crafting Interpreters so scripts can be checked against its test suite
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dialect {
+    #[default]
+    Extended,
+    Book,
+}
+
+impl Dialect {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "extended" => Some(Dialect::Extended),
+            "book" => Some(Dialect::Book),
+            _ => None,
+        }
+    }
+
+    pub fn is_book(self) -> bool {
+        self == Dialect::Book
+    }
+}
+
+/// Format a printed value the way the reference implementation prints it, where that differs
+pub fn display(value: &Literal) -> String {
+    match value {
+        Literal::Number(n) if n.is_infinite() => {
+            if *n > 0.0 { "Infinity" } else { "-Infinity" }.to_owned()
+        }
+        Literal::NativeFunction(_) => String::from("<native fn>"),
+        value => value.to_string(),
+    }
+}
+
+/// The words that are keywords in the book dialect; every other reserved word is an identifier there
+pub const BOOK_KEYWORDS: &[&str] = &[
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
+/// Exit code for a script that fails to scan, parse or resolve, as in the reference implementation
+pub const COMPILE_ERROR_EXIT_CODE: i32 = 65;
+
+/// Exit code for a script that fails while running, as in the reference implementation
+pub const RUNTIME_ERROR_EXIT_CODE: i32 = 70;
+
+/// Split an error of the form `Line N: message` into its line and message
+pub fn split_line(error: &str) -> (Option<usize>, &str) {
+    error
+        .strip_prefix("Line ")
+        .and_then(|rest| {
+            let (line, message) = rest.split_at(rest.find(": ")?);
+            Some((Some(line.parse().ok()?), &message[2..]))
+        })
+        .unwrap_or((None, error))
+}
+
+/// Format a scan, parse or resolve error the way the reference implementation reports them
+pub fn compile_error(error: &str) -> String {
+    match split_line(error) {
+        (Some(line), message) => format!("[line {}] Error: {}", line, book_message(message)),
+        (None, message) => format!("Error: {}", book_message(message)),
+    }
+}
+
+/// Format a parse error the way the reference implementation reports them, at the lexeme
+/// parsing stopped at, or at the end of the source when there is none
+pub fn parse_error(line: usize, lexeme: Option<&str>, message: &str) -> String {
+    let location = match lexeme {
+        Some(lexeme) => format!(" at '{}'", lexeme),
+        None => String::from(" at end"),
+    };
+    format!("[line {}] Error{}: {}", line, location, book_message(message))
+}
+
+/// Format a runtime error the way the reference implementation reports them
+pub fn runtime_error(error: &str) -> String {
+    match split_line(error) {
+        (Some(line), message) => format!("{}\n[line {}]", book_message(message), line),
+        (None, message) => book_message(message),
+    }
+}
+
+// errors whose wording differs in the reference implementation:
+// the text before and after the name they mention, and the reference wording with `{}` for the name
+const BOOK_MESSAGES: &[(&str, &str, &str)] = &[
+    ("Variable '", "' referenced before assignment", "Undefined variable '{}'."),
+    ("Attempted to assign to '", "' before declaration", "Undefined variable '{}'."),
+    ("Undefined property '", "'", "Undefined property '{}'."),
+    ("Type Error: ", " is not callable", "Can only call functions and classes."),
+    (
+        "Type Error: only instances, modules, errors, generators, enums and enum values have properties but got ",
+        "",
+        "Only instances have properties.",
+    ),
+    ("Type Error: only instances have fields but got ", "", "Only instances have fields."),
+    ("Type Error: superclass must be a class but got ", "", "Superclass must be a class."),
+    (
+        "Cannot read local variable '",
+        "' in its own initializer",
+        "Can't read local variable in its own initializer.",
+    ),
+    (
+        "Variable '",
+        "' is already declared in this scope",
+        "Already a variable with this name in this scope.",
+    ),
+    ("Stack overflow: more than ", " nested calls", "Stack overflow."),
+    ("Failed to parse ", "; expected expression", "Expect expression."),
+    ("EOF: No more tokens while parsing a ", " expression", "Expect expression."),
+    ("Expected closing parenthesis for expression", "", "Expect ')' after expression."),
+    ("Invalid assignment target: ", "", "Invalid assignment target."),
+    ("Cannot return from top-level code", "", "Can't return from top-level code."),
+    (
+        "Cannot return a value from an initializer",
+        "",
+        "Can't return a value from an initializer.",
+    ),
+    ("A class cannot inherit from itself", "", "A class can't inherit from itself."),
+    ("Cannot use 'this' outside of a class", "", "Can't use 'this' outside of a class."),
+    ("Cannot use 'super' outside of a class", "", "Can't use 'super' outside of a class."),
+    (
+        "Cannot use 'super' in a class with no superclass",
+        "",
+        "Can't use 'super' in a class with no superclass.",
+    ),
+];
+
+/// Reword an error message the way the reference implementation words it, where they differ
+fn book_message(message: &str) -> String {
+    BOOK_MESSAGES
+        .iter()
+        .find_map(|(prefix, suffix, book)| {
+            let name = message.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some(book.replace("{}", name))
+        })
+        .unwrap_or_else(|| message.to_string())
+}
@@ -1,42 +1,113 @@
 use crate::data::literals::Literal;
-use std::collections::{HashMap, LinkedList};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
+struct Scope {
+    values: HashMap<String, Rc<Literal>>,
+    // the names in this scope declared with `const`
+    constants: HashSet<String>,
+    enclosing: Option<Rc<RefCell<Scope>>>,
+}
+
+/// A handle on the innermost scope of a chain of scopes.
+/// Scopes are shared, so cloning an `Environment` captures the chain
+/// rather than copying it; values defined or assigned through one handle
+/// are visible through every other handle on the same chain
+#[derive(Clone)]
 pub struct Environment {
-    stack: LinkedList<HashMap<String, Rc<Literal>>>,
+    current: Rc<RefCell<Scope>>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
-        let mut stack = LinkedList::new();
-        stack.push_front(HashMap::new());
-        Environment { stack }
+        Environment {
+            current: Rc::new(RefCell::new(Scope {
+                values: HashMap::new(),
+                constants: HashSet::new(),
+                enclosing: None,
+            })),
+        }
     }
 
     pub fn join(&mut self) -> Result<(), String> {
-        self.stack.pop_front().map(|_| ()).ok_or(String::from("Attempted to join in a non-forked environment"))
+        let enclosing = self
+            .current
+            .borrow()
+            .enclosing
+            .clone()
+            .ok_or_else(|| String::from("Attempted to join in a non-forked environment"))?;
+        self.current = enclosing;
+        Ok(())
     }
 
     pub fn fork(&mut self) {
-        self.stack.push_front(HashMap::new())
+        self.current = Rc::new(RefCell::new(Scope {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            enclosing: Some(self.current.clone()),
+        }))
     }
 
     pub fn define(&mut self, name: String, value: Literal) {
-        self.stack.front_mut().map(|values| values.insert(name, Rc::new(value)));
+        let mut scope = self.current.borrow_mut();
+        scope.constants.remove(&name);
+        scope.values.insert(name, Rc::new(value));
+    }
+
+    /// Define a name in the current scope that cannot be assigned to
+    pub fn define_constant(&mut self, name: String, value: Literal) {
+        let mut scope = self.current.borrow_mut();
+        scope.constants.insert(name.clone());
+        scope.values.insert(name, Rc::new(value));
     }
 
-    pub fn get(&self, name: &String) -> Option<Rc<Literal>> {
-        for values in self.stack.iter() {
-            if let Some(i) = values.get(name) {
-                return Some(i.clone())
+    /// Whether the name is a constant defined in the current scope
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.current.borrow().constants.contains(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<Literal>> {
+        let mut scope = self.current.clone();
+        loop {
+            if let Some(i) = scope.borrow().values.get(name) {
+                return Some(i.clone());
             }
+            let enclosing = scope.borrow().enclosing.clone()?;
+            scope = enclosing;
         }
-        None
     }
 
-    pub fn assign(&mut self, name: String, value: Literal) -> Result<Rc<Literal>, String> {
-        let value = Rc::new(value);
-        self.assign_reference(name, value)
+    fn ancestor(&self, depth: usize) -> Option<Rc<RefCell<Scope>>> {
+        let mut scope = self.current.clone();
+        for _ in 0..depth {
+            let enclosing = scope.borrow().enclosing.clone()?;
+            scope = enclosing;
+        }
+        Some(scope)
+    }
+
+    /// A handle on the outermost scope of the chain, which holds the globals
+    pub fn root(&self) -> Environment {
+        let mut scope = self.current.clone();
+        loop {
+            let enclosing = scope.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => scope = enclosing,
+                None => return Environment { current: scope },
+            }
+        }
+    }
+
+    /// Get a value from the scope exactly `depth` scopes out from the current one
+    pub fn get_at(&self, depth: usize, name: &str) -> Option<Rc<Literal>> {
+        self.ancestor(depth)?.borrow().values.get(name).cloned()
     }
 
     pub fn assign_reference(
@@ -44,12 +115,43 @@ impl Environment {
         name: String,
         value: Rc<Literal>,
     ) -> Result<Rc<Literal>, String> {
-        for values in self.stack.iter_mut() {
-            if values.contains_key(&name) {
-                values.insert(name, value.clone());
-                return Ok(value.clone())
+        let mut scope = self.current.clone();
+        loop {
+            if scope.borrow().values.contains_key(&name) {
+                return assign_in(&mut scope.borrow_mut(), name, value);
             }
-        };
+            let enclosing = scope.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => scope = enclosing,
+                None => break,
+            }
+        }
         Err(format!("Attempted to assign to '{}' before declaration", name))
     }
+
+    /// Assign to a name in the scope exactly `depth` scopes out from the current one
+    pub fn assign_reference_at(
+        &mut self,
+        depth: usize,
+        name: String,
+        value: Rc<Literal>,
+    ) -> Result<Rc<Literal>, String> {
+        let scope = self
+            .ancestor(depth)
+            .ok_or_else(|| format!("Attempted to assign to '{}' outside of its scope", name))?;
+        let mut scope = scope.borrow_mut();
+        if !scope.values.contains_key(&name) {
+            return Err(format!("Attempted to assign to '{}' before declaration", name));
+        }
+        assign_in(&mut scope, name, value)
+    }
+}
+
+/// Replace the value of a name defined in the scope, unless it is a constant
+fn assign_in(scope: &mut Scope, name: String, value: Rc<Literal>) -> Result<Rc<Literal>, String> {
+    if scope.constants.contains(&name) {
+        return Err(format!("Cannot assign to constant '{}'", name));
+    }
+    scope.values.insert(name, value.clone());
+    Ok(value)
 }
@@ -1,14 +1,67 @@
-use crate::data::ast::{Expression, ExpressionItem, Statement, StatementItem};
-use crate::data::literals::Literal;
+use crate::data::ast::{
+    Expression, ExpressionItem, FunctionDefinition, MatchArm, MatchPattern, Pattern, Statement,
+    StatementItem,
+};
+use crate::data::callable::{LoxClass, LoxFunction, LoxInstance, NativeFunction};
+use crate::data::enums::EnumValue;
+use crate::data::errors::RuntimeError;
+use crate::data::generator::{
+    GeneratorMethod, GeneratorStatus, GeneratorYielder, LoxGenerator, Resumption,
+};
+use crate::data::integer;
+use crate::data::iterator::LoxIterator;
+use crate::data::literals::{Literal, MapKey};
+use crate::data::module::LoxModule;
 use crate::data::tokens::Token;
+use crate::dialect::{self, Dialect};
 use crate::environment::Environment;
+use crate::modules;
+use crate::natives;
+use corosensei::stack::DefaultStack;
+use corosensei::{Coroutine, CoroutineResult};
+use num_traits::Signed;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 pub struct Interpreter {
     environment: Environment,
+    globals: Environment,
+    // the natives the host registered with `define_native`, which every module's globals also get;
+    // shared with the interpreters of generators
+    host_natives: Rc<RefCell<Vec<Rc<NativeFunction>>>>,
+    // every module imported so far, by canonical path; shared with the interpreters of generators
+    modules: Rc<RefCell<HashMap<PathBuf, Rc<LoxModule>>>>,
+    // the files currently being run, outermost first; imports are relative to the last
+    importing: Vec<PathBuf>,
+    // whether destructuring a value with missing elements is an error rather than binding nil
+    strict_destructuring: bool,
+    dialect: Dialect,
+    // how many Lox function calls are currently running, limited by MAX_CALL_DEPTH; shared with the
+    // interpreters of generators, whose bodies run nested in the calls that resume them
+    call_depth: Rc<Cell<usize>>,
+    // how the generator body this interpreter is running suspends itself, if it is running one
+    yielder: Option<*const GeneratorYielder>,
 }
 
+/// The deepest that Lox function calls can nest before a stack overflow error is raised,
+/// rather than the interpreter itself running out of stack
+pub const MAX_CALL_DEPTH: usize = 2000;
+
+/// The size of the main stack that Lox code runs on.
+/// It is enough for MAX_CALL_DEPTH nested calls, even in a debug build
+pub const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+/// The most stack one nested Lox call takes, with room to spare;
+/// unoptimized builds take several times as much as optimized ones
+const CALL_STACK_SIZE: usize = if cfg!(debug_assertions) { 96 * 1024 } else { 16 * 1024 };
+
+/// The size of each generator's stack. Calls made by a generator's body count towards
+/// MAX_CALL_DEPTH along with the calls resuming it, so it never needs room for more than that
+const GENERATOR_STACK_SIZE: usize = MAX_CALL_DEPTH * CALL_STACK_SIZE;
+
 #[derive(Debug)]
 pub enum LoxData {
     ByValue(Literal),
@@ -16,12 +69,43 @@ pub enum LoxData {
 }
 
 impl LoxData {
-    fn as_ref<'a>(&'a self) -> &'a Literal {
+    fn as_ref(&self) -> &Literal {
         match self {
-            LoxData::ByValue(l) => &l,
+            LoxData::ByValue(l) => l,
             LoxData::ByReference(r) => r.as_ref(),
         }
     }
+
+    fn into_literal(self) -> Literal {
+        match self {
+            LoxData::ByValue(l) => l,
+            LoxData::ByReference(rc) => rc.as_ref().clone(),
+        }
+    }
+}
+
+/// The reasons that evaluation of a statement can stop early
+pub(crate) enum Interrupt {
+    Error(String),
+    // a value thrown by a `throw` statement on the given line
+    Throw(Literal, usize),
+    Return(LoxData),
+    Break(Option<String>),
+    Continue(Option<String>),
+}
+
+/// Why a generator's body stopped running
+enum Stop {
+    // suspended at a yield with the value it gave
+    Yielded(Literal),
+    // finished, by returning or reaching the end of the body
+    Returned(Literal),
+}
+
+impl From<String> for Interrupt {
+    fn from(error: String) -> Self {
+        Interrupt::Error(error)
+    }
 }
 
 impl fmt::Display for LoxData {
@@ -33,58 +117,147 @@ impl fmt::Display for LoxData {
     }
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Interpreter {
     pub fn new() -> Self {
-        Interpreter {
-            environment: Environment::new(),
+        let globals = Environment::new();
+        let mut interpreter = Interpreter {
+            environment: globals.clone(),
+            globals,
+            host_natives: Rc::new(RefCell::new(vec![])),
+            modules: Rc::new(RefCell::new(HashMap::new())),
+            importing: vec![],
+            strict_destructuring: true,
+            dialect: Dialect::default(),
+            call_depth: Rc::new(Cell::new(0)),
+            yielder: None,
+        };
+        natives::define_defaults(&mut interpreter);
+        interpreter
+    }
+
+    /// Expose a Rust function to Lox code as a global with the given name, in the main program
+    /// and in every module it imports. Errors returned by the function are raised as Lox runtime errors
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(Vec<Literal>) -> Result<Literal, String> + 'static,
+    {
+        let native = Rc::new(NativeFunction {
+            name: name.to_owned(),
+            arity,
+            function: Box::new(function),
+        });
+        self.host_natives.borrow_mut().push(native.clone());
+        self.globals
+            .define(name.to_owned(), Literal::NativeFunction(native));
+    }
+
+    /// Define one of the natives every program gets, without it being taken for one of the host's
+    pub(crate) fn define_default<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(Vec<Literal>) -> Result<Literal, String> + 'static,
+    {
+        let native = NativeFunction {
+            name: name.to_owned(),
+            arity,
+            function: Box::new(function),
+        };
+        self.globals
+            .define(name.to_owned(), Literal::NativeFunction(Rc::new(native)));
+    }
+
+    /// Replace the globals with new ones holding the default natives and the host's
+    fn reset_globals(&mut self) {
+        self.globals = Environment::new();
+        natives::define_defaults(self);
+        for native in self.host_natives.borrow().iter() {
+            self.globals
+                .define(native.name.clone(), Literal::NativeFunction(native.clone()));
         }
     }
 
+    /// Set the file the program being interpreted was read from,
+    /// so that its imports are found relative to it
+    pub fn set_script_path(&mut self, path: &Path) {
+        self.importing = vec![modules::canonical(path)];
+    }
+
+    /// Choose whether names missing from a destructured list or map raise an error (the default)
+    /// or are bound to nil
+    pub fn set_strict_destructuring(&mut self, strict: bool) {
+        self.strict_destructuring = strict;
+    }
+
+    /// Choose the dialect to run programs in. The globals are defined afresh, since the
+    /// book dialect has only the `clock` native, but keep the natives the host defined
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+        self.reset_globals();
+        self.environment = self.globals.clone();
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
     pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<(), String> {
         statements
             .iter()
-            .map(|s| self.evaluate_statement(s))
-            .collect::<Result<_, _>>()?;
-        Ok(())
+            .try_for_each(|s| match self.evaluate_statement(s) {
+                Ok(()) => Ok(()),
+                Err(Interrupt::Error(e)) => Err(e),
+                Err(Interrupt::Throw(Literal::Error(error), _)) => Err(match error.line {
+                    Some(line) => format!("Line {}: {}", line, error),
+                    None => format!("{}", error),
+                }),
+                Err(Interrupt::Throw(value, line)) => {
+                    Err(format!("Line {}: Uncaught exception: {}", line, value))
+                }
+                Err(Interrupt::Return(_)) => Err(format!(
+                    "Line {}: Cannot return from top-level code",
+                    s.line()
+                )),
+                Err(Interrupt::Break(_)) | Err(Interrupt::Continue(_)) => Err(format!(
+                    "Line {}: Cannot break or continue outside of a loop",
+                    s.line()
+                )),
+            })
+    }
+
+    fn evaluate_statements(&mut self, statements: &[Statement]) -> Result<(), Interrupt> {
+        statements
+            .iter()
+            .try_for_each(|s| self.evaluate_statement(s))
     }
 
-    fn evaluate_statement(&mut self, statement: &Statement) -> Result<(), String> {
+    fn evaluate_statement(&mut self, statement: &Statement) -> Result<(), Interrupt> {
         match statement.item() {
             StatementItem::ExpressionStatement(expr) => {
                 self.evaluate_expression(expr)?;
             }
             StatementItem::PrintStatement(expr) => {
                 let result = self.evaluate_expression(expr)?;
-                println!("{}", result);
-            }
-            StatementItem::Declaration { name, initializer } => {
-                let value = self.evaluate_expression(initializer)?;
-                self.environment.define(
-                    name.clone(),
-                    match value {
-                        LoxData::ByValue(l) => l,
-                        LoxData::ByReference(rc) => rc.as_ref().clone(),
-                    },
-                );
-            }
-            StatementItem::Block { statements } => {
-                self.environment.fork();
-                let mut result = None;
-                for statement in statements.iter() {
-                    if let Err(e) = self.evaluate_statement(statement) {
-                        result = Some(e);
-                        break;
-                    }
-                }
-
-                self.environment
-                    .join()
-                    .expect("Failed to join on the environment!");
-
-                if let Some(e) = result {
-                    return Err(e);
+                if self.dialect.is_book() {
+                    println!("{}", dialect::display(result.as_ref()));
+                } else {
+                    println!("{}", result);
                 }
             }
+            StatementItem::Declaration {
+                pattern,
+                initializer,
+                constant,
+                ..
+            } => {
+                let value = self.evaluate_expression(initializer)?.into_literal();
+                self.declare_pattern(pattern, value, *constant, statement.line())?;
+            }
+            StatementItem::Block { statements } => self.evaluate_block(statements, None)?,
             StatementItem::IfStatement { test, when_true, when_false } => {
                 let eval_test = self.evaluate_expression(test)?;
                 if as_boolean(eval_test.as_ref()) {
@@ -93,21 +266,874 @@ impl Interpreter {
                     self.evaluate_statement(when_false)?;
                 }
             }
-            StatementItem::WhileStatement { test, body } => {
-                loop {
-                    let test_result = self.evaluate_expression(test)?;
-                    if as_boolean(test_result.as_ref()) {
-                        self.evaluate_statement(body)?;
-                    } else {
+            StatementItem::WhileStatement {
+                test,
+                body,
+                increment,
+                label,
+            } => loop {
+                let test_result = self.evaluate_expression(test)?;
+                if !as_boolean(test_result.as_ref()) {
+                    break;
+                }
+                match self.evaluate_statement(body) {
+                    Ok(()) => (),
+                    Err(Interrupt::Break(target)) if target.is_none() || target == *label => {
                         break
                     }
+                    Err(Interrupt::Continue(target))
+                        if target.is_none() || target == *label => {}
+                    Err(e) => return Err(e),
+                }
+                if let Some(increment) = increment {
+                    self.evaluate_expression(increment)?;
+                }
+            },
+            StatementItem::ForIn {
+                pattern,
+                iterable,
+                body,
+                label,
+            } => {
+                let line = statement.line();
+                let iterable = self.evaluate_expression(iterable)?;
+                let mut iterator = LoxIterator::new(iterable.as_ref())
+                    .map_err(|e| format!("Line {}: {}", line, e))?;
+                while let Some(value) = self.next_element(&mut iterator, line)? {
+                    let mut bindings = vec![];
+                    destructure(pattern, value, self.strict_destructuring, line, &mut bindings)?;
+                    // a fresh scope per element, so closures made in the body capture that element
+                    self.environment.fork();
+                    for (name, value) in bindings {
+                        self.environment.define(name, value);
+                    }
+                    let result = self.evaluate_statement(body);
+                    self.environment
+                        .join()
+                        .expect("Failed to join on the environment!");
+                    match result {
+                        Ok(()) => (),
+                        Err(Interrupt::Break(target)) if target.is_none() || target == *label => {
+                            break
+                        }
+                        Err(Interrupt::Continue(target))
+                            if target.is_none() || target == *label => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            StatementItem::Break { label } => return Err(Interrupt::Break(label.clone())),
+            StatementItem::Continue { label } => {
+                return Err(Interrupt::Continue(label.clone()))
+            }
+            StatementItem::FunctionDeclaration(definition) => {
+                let function = LoxFunction {
+                    definition: definition.clone(),
+                    closure: self.environment.clone(),
+                    is_initializer: false,
+                };
+                self.declare(
+                    definition.name.clone(),
+                    Literal::Function(Rc::new(function)),
+                    false,
+                    statement.line(),
+                )?;
+            }
+            StatementItem::ReturnStatement(expr) => {
+                let value = match expr {
+                    Some(expr) => self.evaluate_expression(expr)?,
+                    None => LoxData::ByValue(Literal::Nil),
+                };
+                return Err(Interrupt::Return(value));
+            }
+            StatementItem::Import { path, name } => {
+                let module = self.import(path, statement.line())?;
+                self.declare(name.clone(), Literal::Module(module), false, statement.line())?;
+            }
+            StatementItem::Export(declaration) => self.evaluate_statement(declaration)?,
+            StatementItem::EnumDeclaration(enumeration) => self.declare(
+                enumeration.name.clone(),
+                Literal::Enum(enumeration.clone()),
+                false,
+                statement.line(),
+            )?,
+            StatementItem::Throw(value) => {
+                let line = statement.line();
+                let value = match self.evaluate_expression(value)?.into_literal() {
+                    // errors made by the `error` native are given the line they are thrown from
+                    Literal::Error(error) if error.line.is_none() => {
+                        Literal::Error(Rc::new(RuntimeError {
+                            line: Some(line),
+                            ..error.as_ref().clone()
+                        }))
+                    }
+                    value => value,
+                };
+                return Err(Interrupt::Throw(value, line));
+            }
+            StatementItem::Match { subject, arms } => {
+                let value = self.evaluate_expression(subject)?.into_literal();
+                for arm in arms.iter() {
+                    let mut bindings = vec![];
+                    if !match_pattern(&arm.pattern, &value, &mut bindings) {
+                        continue;
+                    }
+                    self.environment.fork();
+                    for (name, value) in bindings {
+                        self.environment.define(name, value);
+                    }
+                    let result = self.evaluate_arm(arm);
+                    self.environment
+                        .join()
+                        .expect("Failed to join on the environment!");
+                    if result? {
+                        break;
+                    }
+                }
+            }
+            StatementItem::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let result = match (self.evaluate_block(body, None), catch) {
+                    (Err(Interrupt::Error(e)), Some((name, handler))) => {
+                        let error = RuntimeError::from_message(&e);
+                        self.evaluate_block(handler, Some((name, Literal::Error(Rc::new(error)))))
+                    }
+                    (Err(Interrupt::Throw(value, _)), Some((name, handler))) => {
+                        self.evaluate_block(handler, Some((name, value)))
+                    }
+                    (result, _) => result,
+                };
+                // an interrupt from the finally block replaces the one from the try or catch
+                if let Some(finally) = finally {
+                    self.evaluate_block(finally, None)?;
+                }
+                result?;
+            }
+            StatementItem::ClassDeclaration {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(superclass) => match self.evaluate_expression(superclass)?.as_ref() {
+                        Literal::Class(class) => Some(class.clone()),
+                        v => {
+                            return Err(Interrupt::Error(format!(
+                                "Line {}: Type Error: superclass must be a class but got {}",
+                                superclass.line(),
+                                v
+                            )))
+                        }
+                    },
+                    None => None,
+                };
+
+                // methods close over a scope holding `super` when there is a superclass
+                if let Some(superclass) = &superclass {
+                    self.environment.fork();
+                    self.environment
+                        .define(String::from("super"), Literal::Class(superclass.clone()));
+                }
+                let methods = methods
+                    .iter()
+                    .map(|method| {
+                        let function = LoxFunction {
+                            definition: method.clone(),
+                            closure: self.environment.clone(),
+                            is_initializer: method.name == "init",
+                        };
+                        (method.name.clone(), Rc::new(function))
+                    })
+                    .collect();
+                if superclass.is_some() {
+                    self.environment
+                        .join()
+                        .expect("Failed to join on the environment!");
+                }
+
+                let class = LoxClass {
+                    name: name.clone(),
+                    superclass,
+                    methods,
+                };
+                self.declare(
+                    name.clone(),
+                    Literal::Class(Rc::new(class)),
+                    false,
+                    statement.line(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a module, running it in its own set of globals the first time it is imported
+    fn import(&mut self, path: &str, line: usize) -> Result<Rc<LoxModule>, Interrupt> {
+        let path = modules::find(path, self.importing.last().map(PathBuf::as_path))
+            .map_err(|e| Interrupt::Error(format!("Line {}: {}", line, e)))?;
+        if let Some(start) = self.importing.iter().position(|p| *p == path) {
+            let cycle = self.importing[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            return Err(Interrupt::Error(format!(
+                "Line {}: Import cycle: {}",
+                line,
+                cycle.join(" -> ")
+            )));
+        }
+        if let Some(module) = self.modules.borrow().get(&path) {
+            return Ok(module.clone());
+        }
+
+        let program = modules::compile(&path)
+            .map_err(|e| Interrupt::Error(format!("Line {}: {}", line, e)))?;
+        let previous_globals = self.globals.clone();
+        let previous_environment = self.environment.clone();
+        self.reset_globals();
+        let globals = self.globals.clone();
+        self.environment = globals.clone();
+        self.importing.push(path.clone());
+        let result = self.evaluate_statements(&program);
+        self.importing.pop();
+        self.globals = previous_globals;
+        self.environment = previous_environment;
+        match result {
+            Ok(()) => (),
+            Err(e @ Interrupt::Error(_)) | Err(e @ Interrupt::Throw(..)) => return Err(e),
+            Err(_) => {
+                return Err(Interrupt::Error(format!(
+                    "Line {}: Module {} returned or broke out of its top level",
+                    line,
+                    path.display()
+                )))
+            }
+        }
+
+        let module = Rc::new(LoxModule {
+            exports: modules::exports(&program),
+            path: path.clone(),
+            globals,
+        });
+        self.modules.borrow_mut().insert(path, module.clone());
+        Ok(module)
+    }
+
+    /// Define a name in the current scope, unless a constant of that name is already defined there
+    fn declare(
+        &mut self,
+        name: String,
+        value: Literal,
+        constant: bool,
+        line: usize,
+    ) -> Result<(), Interrupt> {
+        if self.environment.is_constant(&name) {
+            return Err(Interrupt::Error(format!(
+                "Line {}: Cannot redeclare constant '{}'",
+                line, name
+            )));
+        }
+        if constant {
+            self.environment.define_constant(name, value);
+        } else {
+            self.environment.define(name, value);
+        }
+        Ok(())
+    }
+
+    /// Declare every name bound by destructuring a value with a pattern
+    fn declare_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: Literal,
+        constant: bool,
+        line: usize,
+    ) -> Result<(), Interrupt> {
+        let mut bindings = vec![];
+        destructure(pattern, value, self.strict_destructuring, line, &mut bindings)?;
+        for (name, value) in bindings {
+            self.declare(name, value, constant, line)?;
+        }
+        Ok(())
+    }
+
+    /// Get the next value for a for-in loop, or None once the iterator is exhausted
+    fn next_element(
+        &mut self,
+        iterator: &mut LoxIterator,
+        line: usize,
+    ) -> Result<Option<Literal>, Interrupt> {
+        match iterator {
+            LoxIterator::Generator(generator) => {
+                let generator = generator.clone();
+                match self.resume(&generator, Literal::Nil, line)? {
+                    (value, false) => Ok(Some(value)),
+                    (_, true) => Ok(None),
+                }
+            }
+            iterator => Ok(iterator.advance()),
+        }
+    }
+
+    /// Run a generator until it next yields, with `sent` as the value of the yield it is suspended at.
+    /// Returns the value yielded or returned, and whether the generator has finished
+    fn resume(
+        &mut self,
+        generator: &LoxGenerator,
+        sent: Literal,
+        line: usize,
+    ) -> Result<(Literal, bool), Interrupt> {
+        let status = generator.state.borrow().status;
+        match status {
+            GeneratorStatus::Finished => return Ok((Literal::Nil, true)),
+            GeneratorStatus::Created if sent != Literal::Nil => {
+                return Err(Interrupt::Error(format!(
+                    "Line {}: Generator Error: cannot send {} to generator {} before it has started",
+                    line,
+                    sent,
+                    generator.name()
+                )))
+            }
+            _ => (),
+        }
+        match self.run_generator(generator, Resumption::Send(sent), line)? {
+            Stop::Yielded(value) => Ok((value, false)),
+            Stop::Returned(value) => Ok((value, true)),
+        }
+    }
+
+    /// Finish a generator early. A suspended generator is finished as if it had returned
+    /// at the yield, so its pending finally blocks run
+    fn close(&mut self, generator: &LoxGenerator, line: usize) -> Result<(), Interrupt> {
+        let status = generator.state.borrow().status;
+        match status {
+            GeneratorStatus::Finished => return Ok(()),
+            GeneratorStatus::Created => {
+                let body = {
+                    let mut state = generator.state.borrow_mut();
+                    state.status = GeneratorStatus::Finished;
+                    state.body.take()
+                };
+                drop(body);
+                return Ok(());
+            }
+            _ => (),
+        }
+        match self.run_generator(generator, Resumption::Close, line)? {
+            Stop::Yielded(..) => {
+                // dropping the suspended body abandons it without running any more Lox code
+                let body = {
+                    let mut state = generator.state.borrow_mut();
+                    state.status = GeneratorStatus::Finished;
+                    state.body.take()
+                };
+                drop(body);
+                Err(Interrupt::Error(format!(
+                    "Line {}: Generator Error: generator {} yielded while closing",
+                    line,
+                    generator.name()
+                )))
+            }
+            Stop::Returned(_) => Ok(()),
+        }
+    }
+
+    /// Carry on running a generator's body from the yield it is suspended at, or start it
+    fn run_generator(
+        &mut self,
+        generator: &LoxGenerator,
+        resumption: Resumption,
+        line: usize,
+    ) -> Result<Stop, Interrupt> {
+        let depth = self.call_depth.get();
+        let mut body = {
+            let mut state = generator.state.borrow_mut();
+            if state.status == GeneratorStatus::Running {
+                return Err(Interrupt::Error(format!(
+                    "Line {}: Generator Error: generator {} is already running",
+                    line,
+                    generator.name()
+                )));
+            }
+            // resuming a generator nests its body in the current call, like calling a function
+            if depth >= MAX_CALL_DEPTH {
+                return Err(Interrupt::Error(format!(
+                    "Line {}: Stack overflow: more than {} nested calls",
+                    line, MAX_CALL_DEPTH
+                )));
+            }
+            state.status = GeneratorStatus::Running;
+            state.body.take().expect("unfinished generators have a body")
+        };
+        self.call_depth.set(depth + 1);
+        let result = body.resume(resumption);
+        self.call_depth.set(depth);
+
+        let mut state = generator.state.borrow_mut();
+        match result {
+            CoroutineResult::Yield(value) => {
+                state.status = GeneratorStatus::Suspended;
+                state.body = Some(body);
+                Ok(Stop::Yielded(value))
+            }
+            CoroutineResult::Return(result) => {
+                state.status = GeneratorStatus::Finished;
+                result.map(Stop::Returned)
+            }
+        }
+    }
+
+    /// An interpreter for the body of a generator, which runs on its own stack in `environment`
+    fn for_generator(&self, environment: Environment) -> Interpreter {
+        Interpreter {
+            environment,
+            globals: self.globals.clone(),
+            host_natives: self.host_natives.clone(),
+            modules: self.modules.clone(),
+            importing: self.importing.clone(),
+            strict_destructuring: self.strict_destructuring,
+            dialect: self.dialect,
+            call_depth: self.call_depth.clone(),
+            yielder: None,
+        }
+    }
+
+    /// Run the body of a generator to its end, giving the value it returns
+    fn run_generator_body(&mut self, body: &[Statement]) -> Result<Literal, Interrupt> {
+        match self.evaluate_statements(body) {
+            Ok(()) => Ok(Literal::Nil),
+            Err(Interrupt::Return(value)) => Ok(value.into_literal()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run the body of a match arm whose pattern matched, if its guard allows it.
+    /// Returns whether the body ran
+    fn evaluate_arm(&mut self, arm: &MatchArm) -> Result<bool, Interrupt> {
+        if let Some(guard) = &arm.guard {
+            if !as_boolean(self.evaluate_expression(guard)?.as_ref()) {
+                return Ok(false);
+            }
+        }
+        self.evaluate_statement(&arm.body)?;
+        Ok(true)
+    }
+
+    /// Evaluate statements in a new scope, optionally with a variable already defined in it.
+    /// The scope is always left, including when evaluation is interrupted
+    fn evaluate_block(
+        &mut self,
+        statements: &[Statement],
+        binding: Option<(&String, Literal)>,
+    ) -> Result<(), Interrupt> {
+        self.environment.fork();
+        if let Some((name, value)) = binding {
+            self.environment.define(name.clone(), value);
+        }
+        let result = self.evaluate_statements(statements);
+        self.environment
+            .join()
+            .expect("Failed to join on the environment!");
+        result
+    }
+
+    fn call(
+        &mut self,
+        callee: &Literal,
+        arguments: Vec<Literal>,
+        named: Vec<(String, Literal)>,
+        line: usize,
+    ) -> Result<LoxData, Interrupt> {
+        match callee {
+            Literal::Function(function) => self.call_function(function, arguments, named, line),
+            Literal::NativeFunction(function) => {
+                if let Some((name, _)) = named.first() {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Unexpected argument '{}' to {}; native functions only take positional arguments",
+                        line, name, function.name
+                    )));
+                }
+                if arguments.len() != function.arity && self.dialect.is_book() {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Expected {} arguments but got {}.",
+                        line,
+                        function.arity,
+                        arguments.len()
+                    )));
+                }
+                if arguments.len() != function.arity {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Expected {} arguments to {} but got {}",
+                        line,
+                        function.arity,
+                        function.name,
+                        arguments.len()
+                    )));
+                }
+                (function.function)(arguments)
+                    .map(LoxData::ByValue)
+                    .map_err(|e| Interrupt::Error(format!("Line {}: {}", line, e)))
+            }
+            Literal::VariantConstructor(enumeration, tag) => {
+                let variant = &enumeration.variants[*tag];
+                if let Some((name, _)) = named.first() {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Unexpected argument '{}' to {}.{}; variants only take positional arguments",
+                        line, name, enumeration.name, variant.name
+                    )));
+                }
+                if arguments.len() != variant.fields.len() {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Expected {} arguments to {}.{} but got {}",
+                        line,
+                        variant.fields.len(),
+                        enumeration.name,
+                        variant.name,
+                        arguments.len()
+                    )));
+                }
+                Ok(LoxData::ByValue(Literal::Variant(Rc::new(EnumValue {
+                    enumeration: enumeration.clone(),
+                    tag: *tag,
+                    payload: arguments,
+                }))))
+            }
+            Literal::GeneratorMethod(generator, method) => {
+                if let Some((name, _)) = named.first() {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Unexpected argument '{}' to {}; generator methods only take positional arguments",
+                        line, name, method.name()
+                    )));
+                }
+                if arguments.len() != method.arity() {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Expected {} arguments to {} but got {}",
+                        line,
+                        method.arity(),
+                        method.name(),
+                        arguments.len()
+                    )));
+                }
+                let value = match method {
+                    GeneratorMethod::Next => self.resume(generator, Literal::Nil, line)?.0,
+                    GeneratorMethod::Send => {
+                        let sent = arguments.into_iter().next().unwrap_or(Literal::Nil);
+                        self.resume(generator, sent, line)?.0
+                    }
+                    GeneratorMethod::Close => {
+                        self.close(generator, line)?;
+                        Literal::Nil
+                    }
+                };
+                Ok(LoxData::ByValue(value))
+            }
+            Literal::Class(class) => {
+                let instance = Rc::new(LoxInstance::new(class.clone()));
+                match class.find_method("init") {
+                    Some(initializer) => {
+                        self.call_function(
+                            &initializer.bind(instance.clone()),
+                            arguments,
+                            named,
+                            line,
+                        )?;
+                    }
+                    None if self.dialect.is_book() && !arguments.is_empty() => {
+                        return Err(Interrupt::Error(format!(
+                            "Line {}: Expected 0 arguments but got {}.",
+                            line,
+                            arguments.len()
+                        )))
+                    }
+                    None if !arguments.is_empty() || !named.is_empty() => {
+                        return Err(Interrupt::Error(format!(
+                            "Line {}: Expected 0 arguments to {} but got {}",
+                            line,
+                            class.name,
+                            arguments.len() + named.len()
+                        )))
+                    }
+                    None => (),
+                }
+                Ok(LoxData::ByValue(Literal::Instance(instance)))
+            }
+            v => Err(Interrupt::Error(format!(
+                "Line {}: Type Error: {} is not callable",
+                line, v
+            ))),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        arguments: Vec<Literal>,
+        named: Vec<(String, Literal)>,
+        line: usize,
+    ) -> Result<LoxData, Interrupt> {
+        let definition = &function.definition;
+        if self.dialect.is_book() && arguments.len() != definition.params.len() {
+            return Err(Interrupt::Error(format!(
+                "Line {}: Expected {} arguments but got {}.",
+                line,
+                definition.params.len(),
+                arguments.len()
+            )));
+        }
+        // match the arguments to the parameters before anything is evaluated
+        let mut arguments = arguments.into_iter();
+        let mut values: Vec<Option<Literal>> =
+            definition.params.iter().map(|_| arguments.next()).collect();
+        let extra: Vec<Literal> = arguments.collect();
+        if !extra.is_empty() && definition.rest.is_none() {
+            return Err(Interrupt::Error(format!(
+                "Line {}: Too many arguments to {}: expected at most {} but got {}",
+                line,
+                function.name(),
+                values.len(),
+                values.len() + extra.len()
+            )));
+        }
+        for (name, value) in named {
+            match definition.params.iter().position(|param| param.name == name) {
+                Some(i) if values[i].is_some() => {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Argument '{}' to {} is given both by position and by name",
+                        line,
+                        name,
+                        function.name()
+                    )))
+                }
+                Some(i) => values[i] = Some(value),
+                None => {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Unexpected argument '{}' to {}",
+                        line,
+                        name,
+                        function.name()
+                    )))
+                }
+            }
+        }
+        let missing = definition
+            .params
+            .iter()
+            .zip(values.iter())
+            .filter(|(param, value)| value.is_none() && param.default.is_none())
+            .map(|(param, _)| &param.name[..])
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Interrupt::Error(format!(
+                "Line {}: Missing arguments to {}: {}",
+                line,
+                function.name(),
+                missing.join(", ")
+            )));
+        }
+
+        if definition.is_generator {
+            // the body runs later, a piece at a time on its own stack, in the scope holding the parameters
+            let previous = std::mem::replace(&mut self.environment, function.closure.clone());
+            self.environment.fork();
+            let result = self.define_parameters(definition, values, extra);
+            let environment = std::mem::replace(&mut self.environment, previous);
+            result?;
+            let stack = DefaultStack::new(GENERATOR_STACK_SIZE).map_err(|e| {
+                Interrupt::Error(format!("Line {}: Could not start generator {}: {}", line, function.name(), e))
+            })?;
+            let mut interpreter = self.for_generator(environment);
+            let body = definition.clone();
+            let coroutine = Coroutine::with_stack(stack, move |yielder: &GeneratorYielder, _| {
+                interpreter.yielder = Some(yielder);
+                interpreter.run_generator_body(&body.body)
+            });
+            let generator = LoxGenerator::new(definition.clone(), coroutine);
+            return Ok(LoxData::ByValue(Literal::Generator(Rc::new(generator))));
+        }
+
+        let depth = self.call_depth.get();
+        if depth >= MAX_CALL_DEPTH {
+            return Err(Interrupt::Error(format!(
+                "Line {}: Stack overflow: more than {} nested calls",
+                line, MAX_CALL_DEPTH
+            )));
+        }
+        let previous = std::mem::replace(&mut self.environment, function.closure.clone());
+        self.environment.fork();
+        self.call_depth.set(depth + 1);
+        let result = self
+            .define_parameters(definition, values, extra)
+            .and_then(|()| self.evaluate_statements(&definition.body));
+        self.call_depth.set(depth);
+        self.environment = previous;
+
+        match result {
+            // initializers always produce the instance they were called on
+            Ok(()) | Err(Interrupt::Return(_)) if function.is_initializer => function
+                .closure
+                .get_at(0, "this")
+                .map(LoxData::ByReference)
+                .ok_or_else(|| Interrupt::Error(String::from("Initializer called without an instance"))),
+            Ok(()) => Ok(LoxData::ByValue(Literal::Nil)),
+            Err(Interrupt::Return(value)) => Ok(value),
+            Err(e @ Interrupt::Error(_)) | Err(e @ Interrupt::Throw(..)) => Err(e),
+            Err(Interrupt::Break(_)) | Err(Interrupt::Continue(_)) => Err(Interrupt::Error(format!(
+                "Cannot break or continue out of function {}",
+                function.name()
+            ))),
+        }
+    }
+
+    /// Define a function's parameters in the current scope,
+    /// evaluating the defaults of those that were not given a value
+    fn define_parameters(
+        &mut self,
+        definition: &FunctionDefinition,
+        values: Vec<Option<Literal>>,
+        extra: Vec<Literal>,
+    ) -> Result<(), Interrupt> {
+        for (param, value) in definition.params.iter().zip(values) {
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.evaluate_expression(default)?.into_literal(),
+                // missing arguments are reported before the call starts
+                (None, None) => Literal::Nil,
+            };
+            self.environment.define(param.name.clone(), value);
+        }
+        if let Some(rest) = &definition.rest {
+            self.environment
+                .define(rest.clone(), Literal::List(Rc::new(RefCell::new(extra))));
+        }
+        Ok(())
+    }
+
+    fn look_up_variable(&self, name: &str, depth: Option<usize>) -> Option<Rc<Literal>> {
+        match depth {
+            Some(depth) => self.environment.get_at(depth, name),
+            // globals belong to the module the running code was declared in
+            None => self.environment.root().get(name),
+        }
+    }
+
+    fn assign_variable(
+        &mut self,
+        name: &str,
+        depth: Option<usize>,
+        value: Rc<Literal>,
+    ) -> Result<Rc<Literal>, String> {
+        match depth {
+            Some(depth) => self
+                .environment
+                .assign_reference_at(depth, name.to_owned(), value),
+            None => self
+                .environment
+                .root()
+                .assign_reference(name.to_owned(), value),
+        }
+    }
+
+    /// Replace the value held by an assignment target with `update` applied to it,
+    /// evaluating the parts of the target (the object and index) exactly once.
+    /// Returns the old and new values
+    fn update_target<F>(
+        &mut self,
+        target: &Expression,
+        update: F,
+    ) -> Result<(Literal, Literal), Interrupt>
+    where
+        F: FnOnce(&mut Self, &Literal) -> Result<Literal, Interrupt>,
+    {
+        let line = target.line();
+        match target.item() {
+            ExpressionItem::Variable { name, depth } => {
+                let old_value = self.look_up_variable(name, *depth).ok_or(Interrupt::Error(format!(
+                    "Line {}: Variable '{}' referenced before assignment",
+                    line, name
+                )))?;
+                let new_value = update(self, &old_value)?;
+                self.assign_variable(name, *depth, Rc::new(new_value.clone()))
+                    .map_err(|e| Interrupt::Error(format!("Line {}: {}", line, e)))?;
+                Ok((old_value.as_ref().clone(), new_value))
+            }
+            ExpressionItem::Get { object, name } => {
+                let eval_object = self.evaluate_expression(object)?;
+                let instance = match eval_object.as_ref() {
+                    Literal::Instance(instance) => instance.clone(),
+                    v => {
+                        return Err(Interrupt::Error(format!(
+                            "Line {}: Type Error: only instances have fields but got {}",
+                            line, v
+                        )))
+                    }
+                };
+                let old_value = LoxInstance::get(&instance, name)
+                    .ok_or(Interrupt::Error(format!("Line {}: Undefined property '{}'", line, name)))?;
+                let new_value = update(self, &old_value)?;
+                instance.set(name.clone(), Rc::new(new_value.clone()));
+                Ok((old_value.as_ref().clone(), new_value))
+            }
+            ExpressionItem::Index { object, index } => {
+                let eval_object = self.evaluate_expression(object)?;
+                let eval_index = self.evaluate_expression(index)?;
+                let old_value = index_get(eval_object.as_ref(), eval_index.as_ref(), line)?;
+                let new_value = update(self, &old_value)?;
+                index_set(eval_object.as_ref(), eval_index.as_ref(), new_value.clone(), line)?;
+                Ok((old_value, new_value))
+            }
+            _ => Err(Interrupt::Error(format!("Line {}: Invalid assignment target", line))),
+        }
+    }
+
+    /// Assign the elements of a list to a list of assignment targets
+    fn assign_list(
+        &mut self,
+        targets: &[Expression],
+        value: &Literal,
+        line: usize,
+    ) -> Result<(), Interrupt> {
+        let (values, _) =
+            unpack_list(value, targets.len(), false, self.strict_destructuring, line)?;
+        for (target, value) in targets.iter().zip(values) {
+            let target_line = target.line();
+            match target.item() {
+                ExpressionItem::List { elements } => self.assign_list(elements, &value, target_line)?,
+                ExpressionItem::Variable { name, depth } => {
+                    self.assign_variable(name, *depth, Rc::new(value))
+                        .map_err(|e| Interrupt::Error(format!("Line {}: {}", target_line, e)))?;
+                }
+                ExpressionItem::Get { object, name } => {
+                    match self.evaluate_expression(object)?.as_ref() {
+                        Literal::Instance(instance) => instance.set(name.clone(), Rc::new(value)),
+                        v => {
+                            return Err(Interrupt::Error(format!(
+                                "Line {}: Type Error: only instances have fields but got {}",
+                                target_line, v
+                            )))
+                        }
+                    }
+                }
+                ExpressionItem::Index { object, index } => {
+                    let eval_object = self.evaluate_expression(object)?;
+                    let eval_index = self.evaluate_expression(index)?;
+                    index_set(eval_object.as_ref(), eval_index.as_ref(), value, target_line)?;
+                }
+                _ => {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Invalid assignment target",
+                        target_line
+                    )))
                 }
             }
         }
         Ok(())
     }
 
-    fn evaluate_expression(&mut self, expression: &Expression) -> Result<LoxData, String> {
+    fn evaluate_expression(&mut self, expression: &Expression) -> Result<LoxData, Interrupt> {
         use LoxData::*;
         let expression_line = expression.line();
         match expression.item() {
@@ -118,21 +1144,30 @@ impl Interpreter {
             ExpressionItem::Unary { operator, operand } => {
                 let operand_line = operand.line();
                 let eval_operand = self.evaluate_expression(operand)?;
+                if self.dialect.is_book() {
+                    book_operand(operator, eval_operand.as_ref(), operand_line)?;
+                }
                 match operator {
                     Token::Plus => Ok(eval_operand),
                     Token::Minus => match eval_operand.as_ref() {
-                        Literal::Integer(i) => Ok(ByValue(Literal::Integer(-i))),
                         Literal::Number(n) => Ok(ByValue(Literal::Number(-n))),
-                        v => Err(format!(
-                            "Line {}: Type Error: cannot negate {:?}",
+                        v => integer::negate(v).map(ByValue).ok_or_else(|| Interrupt::Error(format!(
+                            "Line {}: Type Error: cannot negate {}",
                             operand_line, v
-                        )),
+                        ))),
                     },
                     Token::Bang => Ok(ByValue(from_boolean(negate(eval_operand.as_ref())))),
-                    _ => Err(format!(
+                    Token::Tilde => {
+                        let v = eval_operand.as_ref();
+                        integer::complement(v).map(ByValue).ok_or_else(|| Interrupt::Error(format!(
+                            "Line {}: Type Error: cannot apply ~ to {}",
+                            operand_line, v
+                        )))
+                    }
+                    _ => Err(Interrupt::Error(format!(
                         "Line {}: Unexpected unary operator {:?}",
                         operand_line, operator
-                    )),
+                    ))),
                 }
             }
             ExpressionItem::Logical {
@@ -154,7 +1189,7 @@ impl Interpreter {
                             return Ok(eval_left)
                         }
                     }
-                    _ => return Err(format!("Line {}: {} is not supported as a logical operator", line, operator))
+                    _ => return Err(Interrupt::Error(format!("Line {}: {} is not supported as a logical operator", line, operator)))
                 }
                 self.evaluate_expression(right)
             }
@@ -166,123 +1201,11 @@ impl Interpreter {
                 let line = left.line();
                 let eval_left = self.evaluate_expression(left)?;
                 let eval_right = self.evaluate_expression(right)?;
-                let eval_left_ref = eval_left.as_ref();
-                let eval_right_ref = eval_right.as_ref();
-                match operator {
-                    Token::Star => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(Literal::Integer(left * right))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(Literal::Number(left * right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::Minus => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(Literal::Integer(left - right))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(Literal::Number(left - right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::Plus => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(Literal::Integer(left + right))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(Literal::Number(left + right))
-                        }
-                        (Literal::StringT(left), Literal::StringT(right)) => ByValue(Literal::StringT(left.clone() + &right[..])),
-                        (Literal::StringT(left), right) => ByValue(Literal::StringT(format!("{}{}", left, right))),
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::Slash => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(Literal::Number(*left as f64 / *right as f64))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(Literal::Number(left / right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::Greater => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(from_boolean(left > right))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(from_boolean(left > right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::GreaterEqual => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(from_boolean(left >= right))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(from_boolean(left >= right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::Lesser => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(from_boolean(left < right))
-                        }
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(from_boolean(left < right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::LesserEqual => Ok(match (eval_left_ref, eval_right_ref) {
-                        (Literal::Integer(left), Literal::Integer(right)) => {
-                            ByValue(from_boolean(left <= right))
-                        }
-                        
-                        (Literal::Integer(_), Literal::Number(_))
-                        | (Literal::Number(_), Literal::Integer(_))
-                        | (Literal::Number(_), Literal::Number(_)) => {
-                            let left = as_f64(eval_left_ref);
-                            let right = as_f64(eval_right_ref);
-                            ByValue(from_boolean(left <= right))
-                        }
-                        _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
-                    }),
-                    Token::EqualEqual => Ok(ByValue(from_boolean(eval_left_ref == eval_right_ref))),
-                    Token::BangEqual => Ok(ByValue(from_boolean(eval_left_ref != eval_right_ref))),
-                    _ => Err(format!(
-                        "Line {}: {} is not a valid operator",
-                        line, operator
-                    )),
+                if self.dialect.is_book() {
+                    book_operands(operator, eval_left.as_ref(), eval_right.as_ref(), line)?;
                 }
+                binary_operation(operator, eval_left.as_ref(), eval_right.as_ref(), line)
+                    .map_err(Interrupt::from)
             }
             ExpressionItem::Ternary {
                 test,
@@ -298,32 +1221,472 @@ impl Interpreter {
                     self.evaluate_expression(when_false)
                 }
             }
-            ExpressionItem::Variable { name } => self
-                .environment
-                .get(&name)
-                .map(|rc| ByReference(rc))
-                .ok_or(format!(
-                    "Line {}: Variable '{}' referenced before assignment",
-                    expression_line, name
-                )),
-            ExpressionItem::Assignment { name, value } => {
-                let result = match self.evaluate_expression(value)? {
-                    ByValue(l) => self
-                        .environment
-                        .assign(name.clone(), l)
-                        .map(|rc| ByReference(rc)),
-                    ByReference(rc) => self
-                        .environment
-                        .assign_reference(name.clone(), rc)
-                        .map(|rc| ByReference(rc)),
+            ExpressionItem::Variable { name, depth } => self
+                .look_up_variable(name, *depth)
+                .map(ByReference)
+            .ok_or(Interrupt::Error(format!(
+                "Line {}: Variable '{}' referenced before assignment",
+                expression_line, name
+            ))),
+            ExpressionItem::Assignment { name, value, depth } => {
+                let value = match self.evaluate_expression(value)? {
+                    ByValue(l) => Rc::new(l),
+                    ByReference(rc) => rc,
                 };
-                if let Err(e) = result {
-                    Err(format!("Line {}: {}", expression_line, e))
-                } else {
-                    result
+                self.assign_variable(name, *depth, value)
+                    .map(ByReference)
+                    .map_err(|e| Interrupt::Error(format!("Line {}: {}", expression_line, e)))
+            }
+            ExpressionItem::CompoundAssignment {
+                target,
+                operator,
+                value,
+            } => {
+                let (_, new_value) = self.update_target(target, |interpreter, old_value| {
+                    let eval_value = interpreter.evaluate_expression(value)?;
+                    binary_operation(operator, old_value, eval_value.as_ref(), expression_line)
+                        .map(LoxData::into_literal)
+                        .map_err(Interrupt::from)
+                })?;
+                Ok(ByValue(new_value))
+            }
+            ExpressionItem::Increment {
+                target,
+                operator,
+                prefix,
+            } => {
+                let (old_value, new_value) = self.update_target(target, |_, old_value| {
+                    match old_value {
+                        Literal::Integer(_) | Literal::BigInteger(_) | Literal::Number(_) => {
+                            binary_operation(operator, old_value, &Literal::Integer(1), expression_line)
+                                .map(LoxData::into_literal)
+                                .map_err(Interrupt::from)
+                        }
+                        v => Err(Interrupt::Error(format!(
+                            "Line {}: Type Error: cannot apply {}{} to {}",
+                            expression_line, operator, operator, v
+                        ))),
+                    }
+                })?;
+                Ok(ByValue(if *prefix { new_value } else { old_value }))
+            }
+            ExpressionItem::Call {
+                callee,
+                arguments,
+                named,
+            } => {
+                let eval_callee = self.evaluate_expression(callee)?;
+                let mut eval_arguments = Vec::with_capacity(arguments.len());
+                for argument in arguments.iter() {
+                    eval_arguments.push(self.evaluate_expression(argument)?.into_literal());
+                }
+                let mut eval_named = Vec::with_capacity(named.len());
+                for (name, argument) in named.iter() {
+                    eval_named.push((name.clone(), self.evaluate_expression(argument)?.into_literal()));
+                }
+                self.call(eval_callee.as_ref(), eval_arguments, eval_named, expression_line)
+            }
+            ExpressionItem::Get { object, name } => {
+                let eval_object = self.evaluate_expression(object)?;
+                match eval_object.as_ref() {
+                    Literal::Instance(instance) => LoxInstance::get(instance, name)
+                        .map(ByReference)
+                        .ok_or(Interrupt::Error(format!(
+                            "Line {}: Undefined property '{}'",
+                            expression_line, name
+                        ))),
+                    Literal::Module(module) => module.get(name).map(ByReference).ok_or(
+                        Interrupt::Error(format!(
+                            "Line {}: Module {} does not export '{}'",
+                            expression_line,
+                            module.path.display(),
+                            name
+                        )),
+                    ),
+                    Literal::Error(error) => error.get(name).map(ByValue).ok_or(Interrupt::Error(
+                        format!("Line {}: Undefined property '{}'", expression_line, name),
+                    )),
+                    Literal::Enum(enumeration) => match enumeration.tag(name) {
+                        Some(tag) if enumeration.variants[tag].fields.is_empty() => {
+                            Ok(ByValue(Literal::Variant(Rc::new(EnumValue {
+                                enumeration: enumeration.clone(),
+                                tag,
+                                payload: vec![],
+                            }))))
+                        }
+                        Some(tag) => Ok(ByValue(Literal::VariantConstructor(enumeration.clone(), tag))),
+                        None => Err(Interrupt::Error(format!(
+                            "Line {}: Enum '{}' has no variant '{}'",
+                            expression_line, enumeration.name, name
+                        ))),
+                    },
+                    Literal::Variant(value) => value.get(name).map(ByValue).ok_or(Interrupt::Error(
+                        format!("Line {}: Undefined property '{}'", expression_line, name),
+                    )),
+                    Literal::Generator(generator) if name == "done" => {
+                        Ok(ByValue(from_boolean(generator.is_finished())))
+                    }
+                    Literal::Generator(generator) => GeneratorMethod::from_name(name)
+                        .map(|method| ByValue(Literal::GeneratorMethod(generator.clone(), method)))
+                        .ok_or(Interrupt::Error(format!(
+                            "Line {}: Undefined property '{}'",
+                            expression_line, name
+                        ))),
+                    v => Err(Interrupt::Error(format!(
+                        "Line {}: Type Error: only instances, modules, errors, generators, enums and enum values have properties but got {}",
+                        expression_line, v
+                    ))),
+                }
+            }
+            ExpressionItem::Set {
+                object,
+                name,
+                value,
+            } => {
+                let eval_object = self.evaluate_expression(object)?;
+                let instance = match eval_object.as_ref() {
+                    Literal::Instance(instance) => instance.clone(),
+                    v => {
+                        return Err(Interrupt::Error(format!(
+                            "Line {}: Type Error: only instances have fields but got {}",
+                            expression_line, v
+                        )))
+                    }
+                };
+                let value = match self.evaluate_expression(value)? {
+                    ByValue(l) => Rc::new(l),
+                    ByReference(rc) => rc,
+                };
+                instance.set(name.clone(), value.clone());
+                Ok(ByReference(value))
+            }
+            ExpressionItem::List { elements } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements.iter() {
+                    values.push(self.evaluate_expression(element)?.into_literal());
+                }
+                Ok(ByValue(Literal::List(Rc::new(RefCell::new(values)))))
+            }
+            ExpressionItem::Map { entries } => {
+                let mut values = BTreeMap::new();
+                for (key, value) in entries.iter() {
+                    let key_line = key.line();
+                    let eval_key = self.evaluate_expression(key)?;
+                    let key = MapKey::from_literal(eval_key.as_ref())
+                        .map_err(|e| Interrupt::Error(format!("Line {}: {}", key_line, e)))?;
+                    values.insert(key, self.evaluate_expression(value)?.into_literal());
+                }
+                Ok(ByValue(Literal::Map(Rc::new(RefCell::new(values)))))
+            }
+            ExpressionItem::Interpolation { parts } => {
+                let mut string = String::new();
+                for part in parts.iter() {
+                    string.push_str(&format!("{}", self.evaluate_expression(part)?));
+                }
+                Ok(ByValue(Literal::StringT(string)))
+            }
+            ExpressionItem::Index { object, index } => {
+                let eval_object = self.evaluate_expression(object)?;
+                let eval_index = self.evaluate_expression(index)?;
+                index_get(eval_object.as_ref(), eval_index.as_ref(), expression_line)
+                    .map(ByValue)
+                    .map_err(Interrupt::from)
+            }
+            ExpressionItem::IndexSet {
+                object,
+                index,
+                value,
+            } => {
+                let eval_object = self.evaluate_expression(object)?;
+                let eval_index = self.evaluate_expression(index)?;
+                let eval_value = self.evaluate_expression(value)?.into_literal();
+                index_set(
+                    eval_object.as_ref(),
+                    eval_index.as_ref(),
+                    eval_value.clone(),
+                    expression_line,
+                )?;
+                Ok(ByValue(eval_value))
+            }
+            ExpressionItem::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let eval_start = self.evaluate_expression(start)?;
+                let eval_end = self.evaluate_expression(end)?;
+                match (eval_start.as_ref(), eval_end.as_ref()) {
+                    (Literal::Integer(start), Literal::Integer(end)) => {
+                        Ok(LoxData::ByValue(Literal::Range {
+                            start: *start,
+                            end: *end,
+                            inclusive: *inclusive,
+                        }))
+                    }
+                    (start, end) if integer::is_integer(start) && integer::is_integer(end) => {
+                        Err(Interrupt::Error(format!(
+                            "Line {}: Range bounds must fit in 64 bits but got {} and {}",
+                            expression_line, start, end
+                        )))
+                    }
+                    (start, end) => Err(Interrupt::Error(format!(
+                        "Line {}: Type Error: range bounds must be integers but got {} and {}",
+                        expression_line, start, end
+                    ))),
+                }
+            }
+            ExpressionItem::Slice { object, start, end } => {
+                let eval_object = self.evaluate_expression(object)?;
+                let length = match eval_object.as_ref() {
+                    Literal::List(elements) => elements.borrow().len(),
+                    Literal::StringT(s) => s.chars().count(),
+                    v => {
+                        return Err(Interrupt::Error(format!(
+                            "Line {}: Type Error: cannot slice {}",
+                            expression_line, v
+                        )))
+                    }
+                };
+                let start = match start {
+                    Some(start) => {
+                        let eval_start = self.evaluate_expression(start)?;
+                        slice_bound(eval_start.as_ref(), length, expression_line)?
+                    }
+                    None => 0,
+                };
+                let end = match end {
+                    Some(end) => {
+                        let eval_end = self.evaluate_expression(end)?;
+                        slice_bound(eval_end.as_ref(), length, expression_line)?
+                    }
+                    None => length,
+                };
+                if start > end {
+                    return Err(Interrupt::Error(format!(
+                        "Line {}: Index Error: slice start {} is after its end {}",
+                        expression_line, start, end
+                    )));
+                }
+                Ok(ByValue(match eval_object.as_ref() {
+                    Literal::List(elements) => Literal::List(Rc::new(RefCell::new(
+                        elements.borrow()[start..end].to_vec(),
+                    ))),
+                    Literal::StringT(s) => {
+                        Literal::StringT(s.chars().skip(start).take(end - start).collect())
+                    }
+                    _ => unreachable!(),
+                }))
+            }
+            ExpressionItem::ListAssignment { targets, value } => {
+                let eval_value = self.evaluate_expression(value)?.into_literal();
+                self.assign_list(targets, &eval_value, expression_line)?;
+                Ok(ByValue(eval_value))
+            }
+            ExpressionItem::Yield(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate_expression(value)?.into_literal(),
+                    None => Literal::Nil,
+                };
+                let yielder = self.yielder.ok_or_else(|| {
+                    Interrupt::Error(format!("Line {}: Cannot yield outside of a generator", expression_line))
+                })?;
+                // SAFETY: the yielder is only set on the interpreter that runs a generator's body,
+                // which lives inside the coroutine that the yielder belongs to
+                match unsafe { &*yielder }.suspend(value) {
+                    Resumption::Send(sent) => Ok(ByValue(sent)),
+                    // closing returns from the yield, so pending finally blocks still run
+                    Resumption::Close => Err(Interrupt::Return(ByValue(Literal::Nil))),
                 }
             }
+            ExpressionItem::Lambda(definition) => {
+                let function = LoxFunction {
+                    definition: definition.clone(),
+                    closure: self.environment.clone(),
+                    is_initializer: false,
+                };
+                Ok(ByValue(Literal::Function(Rc::new(function))))
+            }
+            ExpressionItem::This { depth } => self
+                .look_up_variable("this", *depth)
+                .map(ByReference)
+                .ok_or(Interrupt::Error(format!(
+                    "Line {}: Cannot use 'this' outside of a method",
+                    expression_line
+                ))),
+            ExpressionItem::Super { method, depth } => {
+                let superclass = self.look_up_variable("super", *depth);
+                // `this` is always bound in the scope just inside the one holding `super`
+                let instance = depth
+                    .and_then(|depth| depth.checked_sub(1))
+                    .and_then(|depth| self.environment.get_at(depth, "this"));
+                match (superclass.as_deref(), instance.as_deref()) {
+                    (Some(Literal::Class(superclass)), Some(Literal::Instance(instance))) => {
+                        superclass
+                            .find_method(method)
+                            .map(|method| {
+                                ByValue(Literal::Function(Rc::new(method.bind(instance.clone()))))
+                            })
+                            .ok_or(Interrupt::Error(format!(
+                                "Line {}: Undefined property '{}'",
+                                expression_line, method
+                            )))
+                    }
+                    _ => Err(Interrupt::Error(format!(
+                        "Line {}: Cannot use 'super' outside of a subclass method",
+                        expression_line
+                    ))),
+                }
+            }
+        }
+    }
+}
+
+/// The book dialect only applies unary minus to numbers
+fn book_operand(operator: &Token, operand: &Literal, line: usize) -> Result<(), Interrupt> {
+    match (operator, operand) {
+        (Token::Minus, Literal::Number(_)) | (Token::Bang, _) => Ok(()),
+        _ => Err(Interrupt::Error(format!(
+            "Line {}: Operand must be a number.",
+            line
+        ))),
+    }
+}
+
+/// The book dialect only applies arithmetic and comparison to numbers, and `+` to two numbers or two strings
+fn book_operands(
+    operator: &Token,
+    left: &Literal,
+    right: &Literal,
+    line: usize,
+) -> Result<(), Interrupt> {
+    match (operator, left, right) {
+        (Token::EqualEqual, _, _) | (Token::BangEqual, _, _) => Ok(()),
+        (_, Literal::Number(_), Literal::Number(_)) => Ok(()),
+        (Token::Plus, Literal::StringT(_), Literal::StringT(_)) => Ok(()),
+        (Token::Plus, _, _) => Err(Interrupt::Error(format!(
+            "Line {}: Operands must be two numbers or two strings.",
+            line
+        ))),
+        _ => Err(Interrupt::Error(format!(
+            "Line {}: Operands must be numbers.",
+            line
+        ))),
+    }
+}
+
+/// Apply a binary operator to two evaluated operands
+fn binary_operation(
+    operator: &Token,
+    eval_left_ref: &Literal,
+    eval_right_ref: &Literal,
+    line: usize,
+) -> Result<LoxData, String> {
+    use LoxData::*;
+    // integers are handled on their own, so that they can overflow into big integers;
+    // what is left below involves at least one float
+    if let Some(result) = integer::binary_operation(operator, eval_left_ref, eval_right_ref) {
+        return result.map(ByValue).map_err(|e| format!("Line {}: {}", line, e));
+    }
+    match operator {
+        Token::Star => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number(left * right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Minus => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number(left - right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Plus => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number(left + right))
+            }
+            (Literal::StringT(left), Literal::StringT(right)) => ByValue(Literal::StringT(left.clone() + &right[..])),
+            (Literal::StringT(left), right) => ByValue(Literal::StringT(format!("{}{}", left, right))),
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Slash => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number(left / right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Greater => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(from_boolean(left > right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::GreaterEqual => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(from_boolean(left >= right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Lesser => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(from_boolean(left < right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::LesserEqual => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(from_boolean(left <= right))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Percent => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number(left - right * (left / right).floor()))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::TildeSlash => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number((left / right).floor()))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::StarStar => Ok(match (eval_left_ref, eval_right_ref) {
+            _ if is_number(eval_left_ref) && is_number(eval_right_ref) => {
+                let left = as_f64(eval_left_ref);
+                let right = as_f64(eval_right_ref);
+                ByValue(Literal::Number(left.powf(right)))
+            }
+            _ => return Err(format!("Line {}: {} cannot be applied to the given types", line, operator))
+        }),
+        Token::Ampersand | Token::Pipe | Token::Caret | Token::LesserLesser | Token::GreaterGreater => {
+            Err(format!("Line {}: {} can only be applied to integers", line, operator))
         }
+        Token::EqualEqual => Ok(ByValue(from_boolean(eval_left_ref == eval_right_ref))),
+        Token::BangEqual => Ok(ByValue(from_boolean(eval_left_ref != eval_right_ref))),
+        _ => Err(format!(
+            "Line {}: {} is not a valid operator",
+            line, operator
+        )),
     }
 }
 
@@ -347,10 +1710,332 @@ fn from_boolean(boolean: bool) -> Literal {
     }
 }
 
+/// Read the element of a list, character of a string or value in a map at an index
+fn index_get(object: &Literal, index: &Literal, line: usize) -> Result<Literal, String> {
+    match object {
+        Literal::List(elements) => {
+            let elements = elements.borrow();
+            let i = checked_index(index, elements.len(), line)?;
+            Ok(elements[i].clone())
+        }
+        Literal::StringT(s) => {
+            let i = checked_index(index, s.chars().count(), line)?;
+            Ok(Literal::StringT(
+                s.chars().nth(i).map(String::from).unwrap_or_default(),
+            ))
+        }
+        Literal::Map(entries) => {
+            let key = MapKey::from_literal(index).map_err(|e| format!("Line {}: {}", line, e))?;
+            entries.borrow().get(&key).cloned().ok_or(format!(
+                "Line {}: Key Error: {} is not in the map",
+                line, index
+            ))
+        }
+        v => Err(format!(
+            "Line {}: Type Error: cannot index into {}",
+            line, v
+        )),
+    }
+}
+
+/// Replace the element of a list or value in a map at an index
+fn index_set(object: &Literal, index: &Literal, value: Literal, line: usize) -> Result<(), String> {
+    match object {
+        Literal::List(elements) => {
+            let mut elements = elements.borrow_mut();
+            let i = checked_index(index, elements.len(), line)?;
+            elements[i] = value;
+            Ok(())
+        }
+        Literal::Map(entries) => {
+            let key = MapKey::from_literal(index).map_err(|e| format!("Line {}: {}", line, e))?;
+            entries.borrow_mut().insert(key, value);
+            Ok(())
+        }
+        Literal::StringT(_) => Err(format!(
+            "Line {}: Type Error: strings are immutable",
+            line
+        )),
+        v => Err(format!(
+            "Line {}: Type Error: cannot assign to an index of {}",
+            line, v
+        )),
+    }
+}
+
+/// Check whether a value has the shape of a match pattern, collecting the values of its bindings
+fn match_pattern(
+    pattern: &MatchPattern,
+    value: &Literal,
+    bindings: &mut Vec<(String, Literal)>,
+) -> bool {
+    match pattern {
+        MatchPattern::Wildcard => true,
+        MatchPattern::Binding(name) => {
+            bindings.push((name.clone(), value.clone()));
+            true
+        }
+        MatchPattern::Literal(literal) => literal == value,
+        MatchPattern::Variant {
+            enumeration,
+            variant,
+            fields,
+        } => {
+            let value = match value {
+                Literal::Variant(value)
+                    if value.enumeration.name == *enumeration
+                        && value.variant().name == *variant =>
+                {
+                    value
+                }
+                _ => return false,
+            };
+            match fields {
+                Some(fields) => {
+                    fields.len() == value.payload.len()
+                        && fields
+                            .iter()
+                            .zip(value.payload.iter())
+                            .all(|(pattern, value)| match_pattern(pattern, value, bindings))
+                }
+                None => true,
+            }
+        }
+        MatchPattern::List { elements, rest } => {
+            let values = match value {
+                Literal::List(values) => values.borrow().clone(),
+                _ => return false,
+            };
+            let length_matches = if rest.is_some() {
+                values.len() >= elements.len()
+            } else {
+                values.len() == elements.len()
+            };
+            if !length_matches
+                || !elements
+                    .iter()
+                    .zip(values.iter())
+                    .all(|(pattern, value)| match_pattern(pattern, value, bindings))
+            {
+                return false;
+            }
+            if let Some(rest) = rest.as_ref().filter(|rest| *rest != "_") {
+                let remaining = values[elements.len()..].to_vec();
+                bindings.push((rest.clone(), Literal::List(Rc::new(RefCell::new(remaining)))));
+            }
+            true
+        }
+    }
+}
+
+/// Match a value against a pattern, collecting the value bound to each name in the pattern
+fn destructure(
+    pattern: &Pattern,
+    value: Literal,
+    strict: bool,
+    line: usize,
+    bindings: &mut Vec<(String, Literal)>,
+) -> Result<(), String> {
+    match pattern {
+        Pattern::Name(name) => bindings.push((name.clone(), value)),
+        Pattern::List { elements, rest } => {
+            let (values, remaining) =
+                unpack_list(&value, elements.len(), rest.is_some(), strict, line)?;
+            for (pattern, value) in elements.iter().zip(values) {
+                destructure(pattern, value, strict, line, bindings)?;
+            }
+            if let Some(rest) = rest {
+                bindings.push((rest.clone(), Literal::List(Rc::new(RefCell::new(remaining)))));
+            }
+        }
+        Pattern::Map { entries } => {
+            for (key, pattern) in entries.iter() {
+                let found = match &value {
+                    Literal::Map(map) => map.borrow().get(&MapKey::StringT(key.clone())).cloned(),
+                    Literal::Instance(instance) => {
+                        LoxInstance::get(instance, key).map(|found| found.as_ref().clone())
+                    }
+                    v => {
+                        return Err(format!(
+                            "Line {}: Type Error: cannot destructure {} by key",
+                            line, v
+                        ))
+                    }
+                };
+                let found = match found {
+                    Some(found) => found,
+                    None if strict => {
+                        return Err(format!(
+                            "Line {}: Key Error: {} is missing from the value being destructured",
+                            line, key
+                        ))
+                    }
+                    None => Literal::Nil,
+                };
+                destructure(pattern, found, strict, line, bindings)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split a list being destructured into the values for `count` patterns and the elements left over.
+/// When strict, it is an error for the list to be too short, or too long with no rest pattern
+/// to collect the left over elements; otherwise missing values are nil
+fn unpack_list(
+    value: &Literal,
+    count: usize,
+    has_rest: bool,
+    strict: bool,
+    line: usize,
+) -> Result<(Vec<Literal>, Vec<Literal>), String> {
+    let elements = match value {
+        Literal::List(elements) => elements.borrow().clone(),
+        v => {
+            return Err(format!(
+                "Line {}: Type Error: cannot destructure {} as a list",
+                line, v
+            ))
+        }
+    };
+    if strict && (elements.len() < count || (!has_rest && elements.len() > count)) {
+        return Err(format!(
+            "Line {}: Index Error: expected {}{} elements to destructure but got {}",
+            line,
+            if has_rest { "at least " } else { "" },
+            count,
+            elements.len()
+        ));
+    }
+    let mut elements = elements.into_iter();
+    let values = (0..count)
+        .map(|_| elements.next().unwrap_or(Literal::Nil))
+        .collect();
+    Ok((values, elements.collect()))
+}
+
+/// Check that an index is an integer within the bounds of a list or string of the given length.
+/// Strings are indexed by character rather than by byte
+fn checked_index(index: &Literal, length: usize, line: usize) -> Result<usize, String> {
+    match index {
+        Literal::Integer(i) if *i < 0 => Err(format!(
+            "Line {}: Index Error: negative index {} is not allowed",
+            line, i
+        )),
+        Literal::Integer(i) if *i as usize >= length => Err(format!(
+            "Line {}: Index Error: index {} is out of bounds for length {}",
+            line, i, length
+        )),
+        Literal::Integer(i) => Ok(*i as usize),
+        Literal::BigInteger(i) if i.is_negative() => Err(format!(
+            "Line {}: Index Error: negative index {} is not allowed",
+            line, i
+        )),
+        Literal::BigInteger(i) => Err(format!(
+            "Line {}: Index Error: index {} is out of bounds for length {}",
+            line, i, length
+        )),
+        v => Err(format!(
+            "Line {}: Type Error: indices must be integers but got {}",
+            line, v
+        )),
+    }
+}
+
+/// Check that a slice bound is an integer from 0 up to and including the length being sliced
+fn slice_bound(bound: &Literal, length: usize, line: usize) -> Result<usize, String> {
+    match bound {
+        Literal::Integer(i) if *i < 0 => Err(format!(
+            "Line {}: Index Error: negative slice bound {} is not allowed",
+            line, i
+        )),
+        Literal::Integer(i) if *i as usize > length => Err(format!(
+            "Line {}: Index Error: slice bound {} is out of bounds for length {}",
+            line, i, length
+        )),
+        Literal::Integer(i) => Ok(*i as usize),
+        Literal::BigInteger(i) if i.is_negative() => Err(format!(
+            "Line {}: Index Error: negative slice bound {} is not allowed",
+            line, i
+        )),
+        Literal::BigInteger(i) => Err(format!(
+            "Line {}: Index Error: slice bound {} is out of bounds for length {}",
+            line, i, length
+        )),
+        v => Err(format!(
+            "Line {}: Type Error: slice bounds must be integers but got {}",
+            line, v
+        )),
+    }
+}
+
+fn is_number(literal: &Literal) -> bool {
+    matches!(literal, Literal::Number(_)) || integer::is_integer(literal)
+}
+
 fn as_f64(literal: &Literal) -> f64 {
     match literal {
         Literal::Integer(i) => *i as f64,
+        Literal::BigInteger(i) => integer::to_f64(i),
         Literal::Number(n) => *n,
         _ => panic!("Cannot cast {:?} to f64!", literal),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::fs;
+
+    fn run(interpreter: &mut Interpreter, source: &str) -> Result<(), String> {
+        let mut scanner = Scanner::new(source);
+        scanner.set_dialect(interpreter.dialect());
+        let tokens = scanner.scan_tokens().map_err(|errors| format!("{:?}", errors))?;
+        let mut parser = Parser::new(tokens);
+        parser.set_dialect(interpreter.dialect());
+        let mut program = parser.parse()?;
+        Resolver::new().resolve(&mut program).map_err(|errors| errors.join("\n"))?;
+        interpreter.interpret(program)
+    }
+
+    /// An interpreter with a `record` native that keeps the values it is called with
+    fn recording() -> (Interpreter, Rc<RefCell<Vec<Literal>>>) {
+        let mut interpreter = Interpreter::new();
+        let recorded = Rc::new(RefCell::new(vec![]));
+        let record = recorded.clone();
+        interpreter.define_native("record", 1, move |args| {
+            record.borrow_mut().push(args[0].clone());
+            Ok(Literal::Nil)
+        });
+        (interpreter, recorded)
+    }
+
+    #[test]
+    fn host_natives_reach_imported_modules() {
+        let directory = std::env::temp_dir().join(format!("treelox-natives-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("helper.lox"), "record(\"module\");\nexport var done = true;\n").unwrap();
+        let (mut interpreter, recorded) = recording();
+        interpreter.set_script_path(&directory.join("main.lox"));
+        let result = run(
+            &mut interpreter,
+            "import \"helper.lox\" as helper;\nrecord(helper.done);",
+        );
+        fs::remove_dir_all(&directory).ok();
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            *recorded.borrow(),
+            vec![Literal::StringT("module".to_owned()), Literal::True]
+        );
+    }
+    #[test]
+    fn host_natives_survive_a_change_of_dialect() {
+        let (mut interpreter, recorded) = recording();
+        interpreter.set_dialect(Dialect::Book);
+        assert_eq!(run(&mut interpreter, "record(1);"), Ok(()));
+        assert_eq!(*recorded.borrow(), vec![Literal::Number(1.0)]);
+    }
+}
@@ -1,21 +1,37 @@
+pub mod checker;
 pub mod data;
+pub mod dialect;
 pub mod parser;
+pub mod resolver;
 pub mod scanner;
 pub mod interpeter;
 pub mod environment;
+pub mod natives;
+pub mod modules;
 
-use clap;
 use std::fs;
 use std::io::{self, Write};
 use std::process::exit;
 use data::errors;
+use dialect::Dialect;
 
-const INTERPRETER_DIRECTIVE_HELP: &'static str = 
+const INTERPRETER_DIRECTIVE_HELP: &str = 
 "Interpreter directives:
     .exit - exit the interpreter
     .help - display this text";
 
 fn main() {
+    // the interpreter recurses once per Lox call, so it runs on a thread with a larger stack
+    let interpreter_thread = std::thread::Builder::new()
+        .stack_size(interpeter::STACK_SIZE)
+        .spawn(start)
+        .expect("Failed to start the interpreter thread");
+    if interpreter_thread.join().is_err() {
+        exit(101)
+    }
+}
+
+fn start() {
     let matches = clap::App::new("TreeLox")
         .version("1.0")
         .author("Reid Swan")
@@ -25,34 +41,57 @@ fn main() {
                 .help("The script to run")
                 .required(false),
         )
+        .arg(
+            clap::Arg::with_name("lenient-destructuring")
+                .long("lenient-destructuring")
+                .help("Bind nil to names missing from a destructured value instead of failing"),
+        )
+        .arg(
+            clap::Arg::with_name("dialect")
+                .long("dialect")
+                .takes_value(true)
+                .possible_values(&["extended", "book"])
+                .default_value("extended")
+                .help("The language to accept: this interpreter's extensions, or Lox exactly as in the book"),
+        )
         .get_matches();
 
+    let mut interpeter = interpeter::Interpreter::new();
+    interpeter.set_strict_destructuring(!matches.is_present("lenient-destructuring"));
+    if let Some(dialect) = matches.value_of("dialect").and_then(Dialect::from_name) {
+        interpeter.set_dialect(dialect);
+    }
+
     if let Some(script) = matches.value_of("script") {
         // script mode
-        run_file(script).unwrap();
+        run_file(script, interpeter).unwrap();
     } else {
         // REPL mode
-        run_prompt().unwrap();
+        run_prompt(interpeter).unwrap();
     }
 }
 
-fn run_file(script: &str) -> Result<(), errors::LoxError> {
-    let file_contents = fs::read_to_string(script).map_err(|e| errors::LoxError::IoError(e))?;
-    if let Err(e) = run(&file_contents, &mut interpeter::Interpreter::new(), false) {
-        eprintln!("{}", e)
+fn run_file(script: &str, mut interpeter: interpeter::Interpreter) -> Result<(), errors::LoxError> {
+    let file_contents = fs::read_to_string(script).map_err(errors::LoxError::IoError)?;
+    interpeter.set_script_path(std::path::Path::new(script));
+    if let Err(e) = run(&file_contents, &mut interpeter, false) {
+        eprintln!("{}", e);
+        // the book dialect exits with the same codes as the reference implementation
+        if interpeter.dialect().is_book() {
+            exit(e.exit_code())
+        }
     };
     Ok(())
 }
 
-fn run_prompt() -> Result<(), errors::LoxError> {
+fn run_prompt(mut interpeter: interpeter::Interpreter) -> Result<(), errors::LoxError> {
     let mut input_string = String::new();
-    let mut interpeter = interpeter::Interpreter::new();
     loop {
         print!("> ");
         io::stdout().flush().unwrap();
         io::stdin()
             .read_line(&mut input_string)
-            .map_err(|e| errors::LoxError::IoError(e))?;
+            .map_err(errors::LoxError::IoError)?;
         if input_string.trim().starts_with(".") || input_string.trim().is_empty() {
             interpret_directive(&input_string[..]);
         } else if let Err(e) = run(&input_string, &mut interpeter, true) {
@@ -62,36 +101,87 @@ fn run_prompt() -> Result<(), errors::LoxError> {
     }
 }
 
-fn run(src: &str, interpeter: &mut interpeter::Interpreter, allow_top_level_expr: bool) -> Result<(), String> {
+/// Why running some source failed, which decides the exit code in the book dialect
+enum RunError {
+    Compile(String),
+    Runtime(String),
+}
+
+impl RunError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Compile(_) => dialect::COMPILE_ERROR_EXIT_CODE,
+            RunError::Runtime(_) => dialect::RUNTIME_ERROR_EXIT_CODE,
+        }
+    }
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Compile(e) | RunError::Runtime(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+fn run(src: &str, interpeter: &mut interpeter::Interpreter, allow_top_level_expr: bool) -> Result<(), RunError> {
+    let dialect = interpeter.dialect();
     let mut scanner = scanner::Scanner::new(src);
+    scanner.set_dialect(dialect);
     let tokens = match scanner.scan_tokens() {
+        Err(errors) if dialect.is_book() => {
+            let messages = errors.iter().map(|e| match e {
+                errors::LoxError::ScannerError(data) => {
+                    dialect::compile_error(&format!("Line {}: {}", data.line_no, data.message))
+                }
+                e => format!("{:?}", e),
+            });
+            return Err(RunError::Compile(messages.collect::<Vec<_>>().join("\n")))
+        }
         Err(errors) => {
             let mut err_string = String::from("Failed to scan:\n");
             errors.iter().for_each(|s| {
                 err_string = format!("{}\n{:?}", err_string, s)
             });
-            return Err(err_string)
+            return Err(RunError::Compile(err_string))
         }
         Ok(tokens) => tokens,
     };
-
     let mut parser = parser::Parser::new(tokens);
+    parser.set_dialect(dialect);
     let parse_result = parser.parse();
-    let program = match parse_result {
+    parser.warnings().iter().for_each(|warning| eprintln!("{}", warning));
+    let mut program = match parse_result {
         Ok(program) => program,
         Err(e) => if allow_top_level_expr {
             parser.reset();
             match parser.parse_top_level_expression() {
                 Ok(program) => program,
                 Err(_) => {
-                    return Err(e)
+                    return Err(RunError::Compile(e))
                 }
             }
         } else {
-            return Err(e)
+            // the parser reports errors in the book dialect's format itself
+            return Err(RunError::Compile(e))
         }
     };
-    interpeter.interpret(program)
+
+    // the book dialect has no annotations, and its operand errors happen at run time
+    if !dialect.is_book() {
+        if let Err(errors) = checker::Checker::new().check(&program) {
+            return Err(RunError::Compile(errors.join("\n")));
+        }
+    }
+    // like the parser, the resolver reports errors in the book dialect's format itself
+    let mut resolver = resolver::Resolver::new();
+    resolver.set_dialect(dialect);
+    if let Err(errors) = resolver.resolve(&mut program) {
+        return Err(RunError::Compile(errors.join("\n")));
+    }
+    interpeter.interpret(program).map_err(|e| {
+        RunError::Runtime(if dialect.is_book() { dialect::runtime_error(&e) } else { e })
+    })
 }
 
 fn interpret_directive(command: &str) {
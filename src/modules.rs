@@ -0,0 +1,87 @@
+use crate::checker::Checker;
+use crate::data::ast::{Statement, StatementItem};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The environment variable holding extra directories to search for imported files,
+/// separated the same way as `PATH`
+pub const LOX_PATH: &str = "LOX_PATH";
+
+/// Find the file an import refers to.
+/// Relative paths are looked up next to the importing file first (or in the working directory
+/// when there is no importing file), then in each directory of `LOX_PATH`
+pub fn find(path: &str, importer: Option<&Path>) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    let mut candidates = vec![];
+    if path.is_absolute() {
+        candidates.push(path.to_path_buf());
+    } else {
+        let importer_dir = importer
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        candidates.push(importer_dir.join(path));
+        if let Some(search_path) = env::var_os(LOX_PATH) {
+            candidates.extend(env::split_paths(&search_path).map(|dir| dir.join(path)));
+        }
+    }
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .map(|found| canonical(found))
+        .ok_or_else(|| format!("Cannot find module '{}'", path.display()))
+}
+
+/// The absolute form of a path, used to identify a module however it was imported
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Read, parse, check and resolve a module's source
+pub fn compile(path: &Path) -> Result<Vec<Statement>, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let tokens = Scanner::new(&source).scan_tokens().map_err(|errors| {
+        errors.iter().fold(
+            format!("Failed to scan {}:", path.display()),
+            |message, error| format!("{}\n{:?}", message, error),
+        )
+    })?;
+    let mut parser = Parser::new(tokens);
+    let parsed = parser.parse();
+    for warning in parser.warnings() {
+        eprintln!("In {}: {}", path.display(), warning);
+    }
+    let mut program = parsed.map_err(|e| format!("In {}: {}", path.display(), e))?;
+    Checker::new()
+        .check(&program)
+        .map_err(|errors| format!("In {}: {}", path.display(), errors.join("\n")))?;
+    Resolver::new()
+        .resolve(&mut program)
+        .map_err(|errors| format!("In {}: {}", path.display(), errors.join("\n")))?;
+    Ok(program)
+}
+
+/// The names declared by the `export` statements of a module
+pub fn exports(program: &[Statement]) -> HashSet<String> {
+    program
+        .iter()
+        .flat_map(|statement| match statement.item() {
+            StatementItem::Export(declaration) => match declaration.item() {
+                StatementItem::Declaration { pattern, .. } => {
+                    pattern.names().into_iter().map(String::from).collect()
+                }
+                StatementItem::ClassDeclaration { name, .. } => vec![name.clone()],
+                StatementItem::EnumDeclaration(enumeration) => vec![enumeration.name.clone()],
+                StatementItem::FunctionDeclaration(definition) => vec![definition.name.clone()],
+                _ => vec![],
+            },
+            _ => vec![],
+        })
+        .collect()
+}
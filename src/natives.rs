@@ -0,0 +1,229 @@
+use crate::data::errors::RuntimeError;
+use crate::data::literals::{Literal, MapKey};
+use crate::interpeter::Interpreter;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Register the native functions available to every Lox program
+pub fn define_defaults(interpreter: &mut Interpreter) {
+    interpreter.define_default("clock", 0, clock);
+    if interpreter.dialect().is_book() {
+        return;
+    }
+    interpreter.define_default("str", 1, str);
+    interpreter.define_default("len", 1, len);
+    interpreter.define_default("push", 2, push);
+    interpreter.define_default("pop", 1, pop);
+    interpreter.define_default("keys", 1, keys);
+    interpreter.define_default("has", 2, has);
+    interpreter.define_default("remove", 2, remove);
+    interpreter.define_default("error", 1, error);
+
+    interpreter.define_default("upper", 1, upper);
+    interpreter.define_default("lower", 1, lower);
+    interpreter.define_default("trim", 1, trim);
+    interpreter.define_default("split", 2, split);
+    interpreter.define_default("join", 2, join);
+    interpreter.define_default("find", 2, find);
+    interpreter.define_default("replace", 3, replace);
+    interpreter.define_default("starts_with", 2, starts_with);
+    interpreter.define_default("ends_with", 2, ends_with);
+    interpreter.define_default("repeat", 2, repeat);
+}
+
+fn string_arg<'a>(function: &str, arg: &'a Literal) -> Result<&'a str, String> {
+    match arg {
+        Literal::StringT(s) => Ok(s),
+        v => Err(format!("Type Error: {} expects a string but got {}", function, v)),
+    }
+}
+
+fn from_boolean(boolean: bool) -> Literal {
+    if boolean {
+        Literal::True
+    } else {
+        Literal::False
+    }
+}
+
+/// clock() -> the number of seconds since the unix epoch
+fn clock(_args: Vec<Literal>) -> Result<Literal, String> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| Literal::Number(duration.as_secs_f64()))
+        .map_err(|e| format!("clock: {}", e))
+}
+
+/// str(value) -> the value as it would be printed
+fn str(args: Vec<Literal>) -> Result<Literal, String> {
+    Ok(Literal::StringT(format!("{}", args[0])))
+}
+
+/// len(list | map | string) -> the number of elements, entries or characters
+fn len(args: Vec<Literal>) -> Result<Literal, String> {
+    match &args[0] {
+        Literal::StringT(s) => Ok(Literal::Integer(s.chars().count() as i64)),
+        Literal::List(elements) => Ok(Literal::Integer(elements.borrow().len() as i64)),
+        Literal::Map(entries) => Ok(Literal::Integer(entries.borrow().len() as i64)),
+        v => Err(format!("Type Error: len cannot be applied to {}", v)),
+    }
+}
+
+/// push(list, value) -> appends the value to the end of the list
+fn push(mut args: Vec<Literal>) -> Result<Literal, String> {
+    let value = args.pop().unwrap();
+    match &args[0] {
+        Literal::List(elements) => {
+            elements.borrow_mut().push(value);
+            Ok(Literal::Nil)
+        }
+        v => Err(format!("Type Error: push cannot be applied to {}", v)),
+    }
+}
+
+/// pop(list) -> removes and returns the last element of the list
+fn pop(args: Vec<Literal>) -> Result<Literal, String> {
+    match &args[0] {
+        Literal::List(elements) => elements
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| String::from("Index Error: pop from an empty list")),
+        v => Err(format!("Type Error: pop cannot be applied to {}", v)),
+    }
+}
+
+/// keys(map) -> a list of the keys in the map, in sorted order
+fn keys(args: Vec<Literal>) -> Result<Literal, String> {
+    match &args[0] {
+        Literal::Map(entries) => {
+            let keys = entries.borrow().keys().map(MapKey::to_literal).collect();
+            Ok(Literal::List(Rc::new(RefCell::new(keys))))
+        }
+        v => Err(format!("Type Error: keys cannot be applied to {}", v)),
+    }
+}
+
+/// has(map, key) -> whether the key is in the map
+fn has(args: Vec<Literal>) -> Result<Literal, String> {
+    match &args[0] {
+        Literal::Map(entries) => {
+            let key = MapKey::from_literal(&args[1])?;
+            Ok(from_boolean(entries.borrow().contains_key(&key)))
+        }
+        v => Err(format!("Type Error: has cannot be applied to {}", v)),
+    }
+}
+
+/// remove(map, key) -> removes the key from the map, returning its value
+fn remove(args: Vec<Literal>) -> Result<Literal, String> {
+    match &args[0] {
+        Literal::Map(entries) => {
+            let key = MapKey::from_literal(&args[1])?;
+            entries
+                .borrow_mut()
+                .remove(&key)
+                .ok_or_else(|| format!("Key Error: {} is not in the map", args[1]))
+        }
+        v => Err(format!("Type Error: remove cannot be applied to {}", v)),
+    }
+}
+
+/// error(message) -> an error value with the kind "Error", to be thrown
+fn error(args: Vec<Literal>) -> Result<Literal, String> {
+    let message = string_arg("error", &args[0])?;
+    Ok(Literal::Error(Rc::new(RuntimeError {
+        kind: String::from("Error"),
+        message: message.to_owned(),
+        line: None,
+    })))
+}
+
+/// upper(string) -> the string in upper case
+fn upper(args: Vec<Literal>) -> Result<Literal, String> {
+    Ok(Literal::StringT(string_arg("upper", &args[0])?.to_uppercase()))
+}
+
+/// lower(string) -> the string in lower case
+fn lower(args: Vec<Literal>) -> Result<Literal, String> {
+    Ok(Literal::StringT(string_arg("lower", &args[0])?.to_lowercase()))
+}
+
+/// trim(string) -> the string without leading or trailing whitespace
+fn trim(args: Vec<Literal>) -> Result<Literal, String> {
+    Ok(Literal::StringT(string_arg("trim", &args[0])?.trim().to_owned()))
+}
+
+/// split(string, separator) -> a list of the parts of the string between separators.
+/// An empty separator splits the string into characters
+fn split(args: Vec<Literal>) -> Result<Literal, String> {
+    let s = string_arg("split", &args[0])?;
+    let separator = string_arg("split", &args[1])?;
+    let parts = if separator.is_empty() {
+        s.chars().map(|c| Literal::StringT(c.to_string())).collect()
+    } else {
+        s.split(separator)
+            .map(|part| Literal::StringT(part.to_owned()))
+            .collect()
+    };
+    Ok(Literal::List(Rc::new(RefCell::new(parts))))
+}
+
+/// join(list, separator) -> the elements of the list joined into a string
+fn join(args: Vec<Literal>) -> Result<Literal, String> {
+    let separator = string_arg("join", &args[1])?;
+    match &args[0] {
+        Literal::List(elements) => Ok(Literal::StringT(
+            elements
+                .borrow()
+                .iter()
+                .map(|element| format!("{}", element))
+                .collect::<Vec<_>>()
+                .join(separator),
+        )),
+        v => Err(format!("Type Error: join expects a list but got {}", v)),
+    }
+}
+
+/// find(string, substring) -> the character index of the first occurrence of substring, or nil
+fn find(args: Vec<Literal>) -> Result<Literal, String> {
+    let s = string_arg("find", &args[0])?;
+    let substring = string_arg("find", &args[1])?;
+    Ok(s.find(substring)
+        .map(|byte_index| Literal::Integer(s[..byte_index].chars().count() as i64))
+        .unwrap_or(Literal::Nil))
+}
+
+/// replace(string, from, to) -> the string with every occurrence of from replaced by to
+fn replace(args: Vec<Literal>) -> Result<Literal, String> {
+    let s = string_arg("replace", &args[0])?;
+    let from = string_arg("replace", &args[1])?;
+    let to = string_arg("replace", &args[2])?;
+    Ok(Literal::StringT(s.replace(from, to)))
+}
+
+/// starts_with(string, prefix) -> whether the string starts with prefix
+fn starts_with(args: Vec<Literal>) -> Result<Literal, String> {
+    let s = string_arg("starts_with", &args[0])?;
+    let prefix = string_arg("starts_with", &args[1])?;
+    Ok(from_boolean(s.starts_with(prefix)))
+}
+
+/// ends_with(string, suffix) -> whether the string ends with suffix
+fn ends_with(args: Vec<Literal>) -> Result<Literal, String> {
+    let s = string_arg("ends_with", &args[0])?;
+    let suffix = string_arg("ends_with", &args[1])?;
+    Ok(from_boolean(s.ends_with(suffix)))
+}
+
+/// repeat(string, count) -> the string repeated count times
+fn repeat(args: Vec<Literal>) -> Result<Literal, String> {
+    let s = string_arg("repeat", &args[0])?;
+    match &args[1] {
+        Literal::Integer(count) if *count >= 0 => Ok(Literal::StringT(s.repeat(*count as usize))),
+        v => Err(format!(
+            "Type Error: repeat expects a non-negative integer count but got {}",
+            v
+        )),
+    }
+}
@@ -1,13 +1,31 @@
-use crate::data::ast::{Expression, ExpressionItem, Statement, StatementItem};
+use crate::data::ast::{
+    Expression, ExpressionItem, FunctionDefinition, MatchArm, MatchPattern, Parameter, Pattern,
+    Statement, StatementItem,
+};
+use crate::data::enums::{LoxEnum, Variant};
+use crate::data::integer;
 use crate::data::literals::Literal;
-use crate::data::tokens::{Token, TokenMeta};
+use crate::data::tokens::{StringPart, Token, TokenMeta};
+use crate::data::types::Type;
+use crate::dialect::{self, Dialect};
+use std::rc::Rc;
 
 pub struct Parser {
     tokens: Vec<TokenMeta>,
     current: usize,
+    warnings: Vec<String>,
+    // the position of the `=>` ending the match arm guard being parsed, if any
+    guard_arrow: Option<usize>,
+    // whether the statement being parsed is a match arm's body, whose `;` can be left out
+    arm_body: bool,
+    // the position of the token the first error happened at, which the book dialect reports
+    error_token: Option<usize>,
+    dialect: Dialect,
 }
 
 type ParseResult<T> = Result<T, String>;
+// the positional and named arguments of a call
+type Arguments = (Vec<Expression>, Vec<(String, Expression)>);
 
 macro_rules! binary_expression_parser {
     ($name:ident,$next:path,$first_match:path,$($rest_match:path),*) => {
@@ -96,6 +114,14 @@ macro_rules! consume {
             ))
         }
     }};
+    // with the reference implementation's wording of the error for the book dialect
+    ($self:ident, $token_type:path, $book_message:expr) => {{
+        if $self.dialect.is_book() && !match_head!($self, $token_type) {
+            Err($self.error_here($book_message))
+        } else {
+            consume!($self, $token_type)
+        }
+    }};
 }
 
 impl Parser {
@@ -103,17 +129,54 @@ impl Parser {
         Parser {
             tokens: src,
             current: 0,
+            warnings: vec![],
+            guard_arrow: None,
+            arm_body: false,
+            error_token: None,
+            dialect: Dialect::default(),
         }
     }
 
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    /// Problems found while parsing that do not stop the program from running
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
     pub fn parse(&mut self) -> ParseResult<Vec<Statement>> {
         let mut statements = vec![];
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(error) if self.dialect.is_book() => return Err(self.book_error(&error)),
+                Err(error) => return Err(error),
+            }
         }
         Ok(statements)
     }
 
+    /// An error reported the way the reference implementation does, at the token it happened at
+    fn book_error(&self, error: &str) -> String {
+        let (_, message) = dialect::split_line(error);
+        match self.error_token.and_then(|position| self.tokens.get(position)) {
+            Some(token) => dialect::parse_error(token.line(), Some(&token.item().lexeme()), message),
+            None => dialect::parse_error(self.last_line(), None, message),
+        }
+    }
+
+    /// An error at the next token, or at the end of the source
+    fn error_here(&self, message: &str) -> String {
+        let line = self.peek().map_or_else(|| self.last_line(), |token| token.line());
+        format!("Line {}: {}", line, message)
+    }
+
+    fn last_line(&self) -> usize {
+        self.tokens.last().map_or(1, |token| token.line())
+    }
+
     pub fn parse_top_level_expression(&mut self) -> ParseResult<Vec<Statement>> {
         self.expression()
             .map(|expr| vec![Statement::new(StatementItem::PrintStatement(expr), 1)])
@@ -125,7 +188,10 @@ impl Parser {
         while !self.is_at_end() {
             let token_meta = self.peek().unwrap();
             match token_meta.item() {
-                Class | Fun | Var | For | If | While | Print | Return => return,
+                Class | Const | Enum | Fun | Var | For | If | While | Print | Return | Break | Continue | Throw | Try
+                | Import | Export | Match => {
+                    return
+                }
                 Semicolon => {
                     self.advance();
                     return;
@@ -140,79 +206,805 @@ impl Parser {
         self.current >= self.tokens.len()
     }
 
-    // declaration := <var_declaration> | <statement>
+    // declaration := <var_declaration> | <fun_declaration> | <class_declaration> | <enum_declaration>
+    //              | <import_declaration> | <export_declaration> | <statement>
     fn declaration(&mut self) -> ParseResult<Statement> {
-        let result = if match_head!(self, Token::Var) {
+        let result = if match_head!(self, Token::Import) {
+            self.import_declaration()
+        } else if match_head!(self, Token::Export) {
+            self.export_declaration()
+        } else if match_head!(self, Token::Var) || match_head!(self, Token::Const) {
             self.var_declaration()
+        } else if match_head!(self, Token::Fun) && !self.is_lambda() {
+            self.fun_declaration()
+        } else if match_head!(self, Token::Class) {
+            self.class_declaration()
+        } else if match_head!(self, Token::Enum) {
+            self.enum_declaration()
         } else {
             self.statement()
         };
 
-        if let Err(_) = &result {
+        if result.is_err() {
+            self.error_token.get_or_insert(self.current);
             self.synchronize();
         }
 
         result
     }
 
-    // var_declaration := var <id> = <expression>;
+    // import_declaration := import <string> as <id> ;
+    fn import_declaration(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Import)?;
+        let path = match self.peek().map(|t| t.item_clone()) {
+            Some(Token::Literal(Literal::StringT(path))) => {
+                self.advance();
+                path
+            }
+            Some(t) => {
+                return Err(format!(
+                    "Line {}: Expected a module path string but got {}",
+                    line, t
+                ))
+            }
+            None => return Err(String::from("EOF: Expected a module path string but got EOF")),
+        };
+        consume!(self, Token::As)?;
+        let name = self.identifier()?;
+        self.end_statement("Expect ';' after import.")?;
+        Ok(Statement::new(StatementItem::Import { path, name }, line))
+    }
+
+    // export_declaration := export ( <var_declaration> | <fun_declaration> | <class_declaration> | <enum_declaration> )
+    fn export_declaration(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Export)?;
+        let declaration = if match_head!(self, Token::Var) || match_head!(self, Token::Const) {
+            self.var_declaration()?
+        } else if match_head!(self, Token::Fun) {
+            self.fun_declaration()?
+        } else if match_head!(self, Token::Class) {
+            self.class_declaration()?
+        } else if match_head!(self, Token::Enum) {
+            self.enum_declaration()?
+        } else {
+            return Err(format!(
+                "Line {}: Expected a var, const, fun, class or enum declaration after export",
+                line
+            ));
+        };
+        Ok(Statement::new(
+            StatementItem::Export(Box::new(declaration)),
+            line,
+        ))
+    }
+
+    // var_declaration := ( var | const ) ( <id> <annotation>? | <pattern> ) = <expression>;
+    // in the book dialect: var <id> ( = <expression> )?;
     fn var_declaration(&mut self) -> ParseResult<Statement> {
-        consume!(self, Token::Var)?;
-        let identifier = self
+        let constant = match_head!(self, Token::Const);
+        let line = if constant {
+            consume!(self, Token::Const)?
+        } else {
+            consume!(self, Token::Var)?
+        };
+        let pattern = if self.dialect.is_book() {
+            Pattern::Name(self.expect_identifier("Expect variable name.")?)
+        } else {
+            self.pattern()?
+        };
+        if let Some(duplicate) = first_duplicate(&pattern.names()) {
+            return Err(format!(
+                "Line {}: '{}' is bound more than once in the same declaration",
+                line, duplicate
+            ));
+        }
+        let annotation = match pattern {
+            Pattern::Name(_) => self.annotation()?,
+            _ => None,
+        };
+        let expression = if self.dialect.is_book() && match_head!(self, Token::Semicolon) {
+            Expression::new(ExpressionItem::Literal { value: Literal::Nil }, line)
+        } else {
+            consume!(self, Token::Equal, "Expect ';' after variable declaration.")?;
+            self.expression()?
+        };
+        self.end_statement("Expect ';' after variable declaration.")?;
+        Ok(Statement::new(
+            StatementItem::Declaration {
+                pattern,
+                initializer: expression,
+                constant,
+                annotation,
+            },
+            line,
+        ))
+    }
+
+    // pattern := <id>
+    //          | [ ( <pattern> ( , <pattern> )* )? ( , ... <id> )? ]
+    //          | { ( <id> ( : <pattern> )? ( , <id> ( : <pattern> )? )* )? }
+    fn pattern(&mut self) -> ParseResult<Pattern> {
+        if match_head!(self, Token::LeftBracket) {
+            self.advance();
+            let mut elements = vec![];
+            let mut rest = None;
+            while !match_head!(self, Token::RightBracket) {
+                if match_head!(self, Token::Ellipsis) {
+                    self.advance();
+                    rest = Some(self.identifier()?);
+                    break;
+                }
+                elements.push(self.pattern()?);
+                if match_head!(self, Token::Comma) {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+            consume!(self, Token::RightBracket)?;
+            Ok(Pattern::List { elements, rest })
+        } else if match_head!(self, Token::LeftBrace) {
+            self.advance();
+            let mut entries = vec![];
+            while !match_head!(self, Token::RightBrace) {
+                let key = self.identifier()?;
+                let pattern = if match_head!(self, Token::Colon) {
+                    self.advance();
+                    self.pattern()?
+                } else {
+                    Pattern::Name(key.clone())
+                };
+                entries.push((key, pattern));
+                if match_head!(self, Token::Comma) {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+            consume!(self, Token::RightBrace)?;
+            Ok(Pattern::Map { entries })
+        } else {
+            self.identifier().map(Pattern::Name)
+        }
+    }
+
+    // enum_declaration := enum <id> { <variant> ( , <variant> )* ,? }
+    // variant := <id> ( '(' <id> ( , <id> )* ')' )?
+    fn enum_declaration(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Enum)?;
+        let name = self.identifier()?;
+        consume!(self, Token::LeftBrace)?;
+        let mut variants: Vec<Variant> = vec![];
+        while !match_head!(self, Token::RightBrace) {
+            let variant = self.identifier()?;
+            if variants.iter().any(|v| v.name == variant) {
+                return Err(format!(
+                    "Line {}: Duplicate variant '{}' in enum '{}'",
+                    line, variant, name
+                ));
+            }
+            let mut fields = vec![];
+            if match_head!(self, Token::LeftParen) {
+                self.advance();
+                loop {
+                    let field = self.identifier()?;
+                    if field == "tag" || fields.contains(&field) {
+                        return Err(format!(
+                            "Line {}: '{}' cannot be used as a field name of {}.{}",
+                            line, field, name, variant
+                        ));
+                    }
+                    fields.push(field);
+                    if match_head!(self, Token::Comma) {
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                consume!(self, Token::RightParen)?;
+            }
+            variants.push(Variant {
+                name: variant,
+                fields,
+            });
+            if match_head!(self, Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        consume!(self, Token::RightBrace)?;
+        if variants.is_empty() {
+            return Err(format!(
+                "Line {}: Enum '{}' must have at least one variant",
+                line, name
+            ));
+        }
+        Ok(Statement::new(
+            StatementItem::EnumDeclaration(Rc::new(LoxEnum { name, variants })),
+            line,
+        ))
+    }
+
+    // class_declaration := class <id> ( < <id> )? { <function>* }
+    fn class_declaration(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Class)?;
+        let name = self.expect_identifier("Expect class name.")?;
+        let superclass = if match_head!(self, Token::Lesser) {
+            let superclass_line = consume!(self, Token::Lesser)?;
+            let superclass_name = self.expect_identifier("Expect superclass name.")?;
+            Some(Expression::new(
+                ExpressionItem::Variable {
+                    name: superclass_name,
+                    depth: None,
+                },
+                superclass_line,
+            ))
+        } else {
+            None
+        };
+        consume!(self, Token::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = vec![];
+        while !match_head!(self, Token::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function("method")?));
+        }
+        consume!(self, Token::RightBrace, "Expect '}' after class body.")?;
+        Ok(Statement::new(
+            StatementItem::ClassDeclaration {
+                name,
+                superclass,
+                methods,
+            },
+            line,
+        ))
+    }
+
+    // fun_declaration := fun *? <function>
+    fn fun_declaration(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Fun)?;
+        let is_generator = match_head!(self, Token::Star) && !self.dialect.is_book();
+        if is_generator {
+            self.advance();
+        }
+        let function = FunctionDefinition {
+            is_generator,
+            ..self.function("function")?
+        };
+        Ok(Statement::new(
+            StatementItem::FunctionDeclaration(Rc::new(function)),
+            line,
+        ))
+    }
+
+    // function := <id> '(' <parameters>? ')' <annotation>? <block>
+    // where `kind` names what is declared in the book dialect's errors
+    fn function(&mut self, kind: &str) -> ParseResult<FunctionDefinition> {
+        let name = self.expect_identifier(&format!("Expect {} name.", kind))?;
+        if self.dialect.is_book() && !match_head!(self, Token::LeftParen) {
+            return Err(self.error_here(&format!("Expect '(' after {} name.", kind)));
+        }
+        let (params, rest) = self.parameters(&name)?;
+        let return_type = self.annotation()?;
+        if self.dialect.is_book() && !match_head!(self, Token::LeftBrace) {
+            return Err(self.error_here(&format!("Expect '{{' before {} body.", kind)));
+        }
+        let body = self.block_statements()?;
+        Ok(FunctionDefinition {
+            name,
+            params,
+            rest,
+            body,
+            is_generator: false,
+            return_type,
+        })
+    }
+
+    // annotation := : <id>, where the name is one of the types the checker knows
+    fn annotation(&mut self) -> ParseResult<Option<Type>> {
+        if !match_head!(self, Token::Colon) {
+            return Ok(None);
+        }
+        let line = consume!(self, Token::Colon)?;
+        let name = self.identifier()?;
+        Type::from_name(&name).map(Some).ok_or_else(|| {
+            format!(
+                "Line {}: Unknown type '{}'; expected Any, Int, Num, Str, Bool or Nil",
+                line, name
+            )
+        })
+    }
+
+    // parses a parenthesised parameter list:
+    // '(' ( <parameter> ( , <parameter> )* ( , ... <id> )? | ... <id> )? ')'
+    // parameter := <id> <annotation>? ( = <expression> )?
+    fn parameters(
+        &mut self,
+        function_name: &str,
+    ) -> ParseResult<(Vec<Parameter>, Option<String>)> {
+        let line = consume!(self, Token::LeftParen)?;
+        let mut params: Vec<Parameter> = vec![];
+        let mut rest = None;
+        while !match_head!(self, Token::RightParen) {
+            let is_rest = match_head!(self, Token::Ellipsis);
+            if is_rest {
+                self.advance();
+            }
+            let name = self.expect_identifier("Expect parameter name.")?;
+            if params.iter().any(|p| p.name == name) || rest.as_ref() == Some(&name) {
+                return Err(format!(
+                    "Line {}: Duplicate parameter '{}' in function '{}'",
+                    line, name, function_name
+                ));
+            }
+            if is_rest {
+                if match_head!(self, Token::Comma) {
+                    return Err(format!(
+                        "Line {}: The rest parameter '{}' must be the last parameter of '{}'",
+                        line, name, function_name
+                    ));
+                }
+                rest = Some(name);
+                break;
+            }
+            let annotation = self.annotation()?;
+            let default = if match_head!(self, Token::Equal) && !self.dialect.is_book() {
+                self.advance();
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            params.push(Parameter {
+                name,
+                default,
+                annotation,
+            });
+            if match_head!(self, Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        consume!(self, Token::RightParen, "Expect ')' after parameters.")?;
+        Ok((params, rest))
+    }
+
+    /// `fun` begins an anonymous function rather than a declaration when it is followed by '(' or '*('
+    fn is_lambda(&self) -> bool {
+        if self.dialect.is_book() {
+            return false;
+        }
+        let token_at = |offset: usize| self.tokens.get(self.current + offset).map(|t| t.item());
+        match token_at(1) {
+            Some(Token::LeftParen) => true,
+            Some(Token::Star) => matches!(token_at(2), Some(Token::LeftParen)),
+            _ => false,
+        }
+    }
+
+    /// a '(' begins an arrow function rather than a grouping when
+    /// the matching ')' is followed by `=>`
+    fn is_arrow_function(&self) -> bool {
+        let mut depth = 0;
+        for (offset, token_meta) in self.tokens[self.current..].iter().enumerate() {
+            match token_meta.item() {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+                _ => (),
+            }
+            if depth == 0 {
+                let arrow = self.current + offset + 1;
+                return matches!(self.tokens.get(arrow).map(|t| t.item()), Some(Token::Arrow))
+                    && self.guard_arrow != Some(arrow);
+            }
+        }
+        false
+    }
+
+    // lambda := fun *? '(' <parameters>? ')' <annotation>? <block> | '(' <parameters>? ')' => <expression>
+    fn lambda(&mut self) -> ParseResult<Expression> {
+        let line = self.peek().map(|t| t.line()).unwrap_or_default();
+        let name = String::from("lambda");
+        let definition = if match_head!(self, Token::Fun) {
+            self.advance();
+            let is_generator = match_head!(self, Token::Star);
+            if is_generator {
+                self.advance();
+            }
+            let (params, rest) = self.parameters(&name)?;
+            let return_type = self.annotation()?;
+            let body = self.block_statements()?;
+            FunctionDefinition {
+                name,
+                params,
+                rest,
+                body,
+                is_generator,
+                return_type,
+            }
+        } else {
+            let (params, rest) = self.parameters(&name)?;
+            consume!(self, Token::Arrow)?;
+            let value = self.expression()?;
+            let value_line = value.line();
+            let body = vec![Statement::new(
+                StatementItem::ReturnStatement(Some(value)),
+                value_line,
+            )];
+            FunctionDefinition {
+                name,
+                params,
+                rest,
+                body,
+                is_generator: false,
+                return_type: None,
+            }
+        };
+        Ok(Expression::new(
+            ExpressionItem::Lambda(Rc::new(definition)),
+            line,
+        ))
+    }
+
+    fn identifier(&mut self) -> ParseResult<String> {
+        let token_meta = self
             .peek()
-            .ok_or(format!("EOF: Expected identifier but got EOF"))?;
-        let line = identifier.line();
-        match identifier.item_clone() {
+            .ok_or_else(|| String::from("EOF: Expected identifier but got EOF"))?;
+        let line = token_meta.line();
+        match token_meta.item_clone() {
             Token::Literal(Literal::Identifier(name)) => {
                 self.advance();
-                consume!(self, Token::Equal)?;
-                let expression = self.expression()?;
-                consume!(self, Token::Semicolon)?;
-                Ok(Statement::new(
-                    StatementItem::Declaration {
-                        name: name.clone(),
-                        initializer: expression,
-                    },
-                    line,
-                ))
+                Ok(name)
             }
-            t => Err(format!("EOF: Expected identifier but got {}", t)),
+            t => Err(format!("Line {}: Expected identifier but got {}", line, t)),
+        }
+    }
+
+    // an identifier, whose absence the book dialect reports with the reference implementation's `book_message`
+    fn expect_identifier(&mut self, book_message: &str) -> ParseResult<String> {
+        let is_identifier = matches!(
+            self.peek().map(|t| t.item()),
+            Some(Token::Literal(Literal::Identifier(_)))
+        );
+        if self.dialect.is_book() && !is_identifier {
+            return Err(self.error_here(book_message));
         }
+        self.identifier()
     }
 
     // statement := <print_statement> | <expression_statement> | <block> | <if_statement>
+    //            | <while_statement> | <for_statement> | <return_statement>
+    //            | <break_statement> | <continue_statement> | <labelled_statement>
+    //            | <throw_statement> | <try_statement> | <match_statement>
     fn statement(&mut self) -> ParseResult<Statement> {
-        if match_head!(self, Token::Print) {
+        if self.is_label() {
+            self.labelled_statement()
+        } else if match_head!(self, Token::Print) {
             self.print_statement()
-        } else if match_head!(self, Token::LeftBrace) {
+        } else if match_head!(self, Token::LeftBrace) && !self.is_map_literal() {
             self.block()
         } else if match_head!(self, Token::If) {
             self.if_statement()
         } else if match_head!(self, Token::While) {
-            self.while_statement()
+            self.while_statement(None)
         } else if match_head!(self, Token::For) {
-            self.for_statement()
+            self.for_statement(None)
+        } else if match_head!(self, Token::Return) {
+            self.return_statement()
+        } else if match_head!(self, Token::Break) {
+            self.break_statement()
+        } else if match_head!(self, Token::Continue) {
+            self.continue_statement()
+        } else if match_head!(self, Token::Throw) {
+            self.throw_statement()
+        } else if match_head!(self, Token::Try) {
+            self.try_statement()
+        } else if match_head!(self, Token::Match) {
+            self.match_statement()
         } else {
             self.expression_statement()
         }
     }
 
+    // return_statement := return <expression>? ;
+    fn return_statement(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Return)?;
+        let value = if self.at_statement_end() {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.end_statement("Expect ';' after return value.")?;
+        Ok(Statement::new(StatementItem::ReturnStatement(value), line))
+    }
+
+    /// a '{' at the start of a statement begins a map literal rather than a block
+    /// when it is followed by `<token> :` that is not a labelled loop
+    fn is_map_literal(&self) -> bool {
+        let token_at = |offset: usize| self.tokens.get(self.current + offset).map(|t| t.item());
+        let is_entry = matches!(token_at(2), Some(Token::Colon))
+            && !matches!(token_at(1), Some(Token::RightBrace));
+        let is_labelled_loop = matches!(token_at(3), Some(Token::While) | Some(Token::For));
+        is_entry && !is_labelled_loop
+    }
+
+    fn is_label(&self) -> bool {
+        let is_identifier = matches!(
+            self.peek().map(|t| t.item()),
+            Some(Token::Literal(Literal::Identifier(_)))
+        );
+        is_identifier && matches!(self.peek_next().map(|t| t.item()), Some(Token::Colon))
+    }
+
+    // labelled_statement := <id> : ( <while_statement> | <for_statement> )
+    fn labelled_statement(&mut self) -> ParseResult<Statement> {
+        let label = self.identifier()?;
+        let line = consume!(self, Token::Colon)?;
+        if match_head!(self, Token::While) {
+            self.while_statement(Some(label))
+        } else if match_head!(self, Token::For) {
+            self.for_statement(Some(label))
+        } else {
+            Err(format!(
+                "Line {}: Expected a loop after label '{}'",
+                line, label
+            ))
+        }
+    }
+
+    // break_statement := break <id>? ;
+    fn break_statement(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Break)?;
+        let label = self.loop_label()?;
+        self.end_statement("Expect ';' after 'break'.")?;
+        Ok(Statement::new(StatementItem::Break { label }, line))
+    }
+
+    // continue_statement := continue <id>? ;
+    fn continue_statement(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Continue)?;
+        let label = self.loop_label()?;
+        self.end_statement("Expect ';' after 'continue'.")?;
+        Ok(Statement::new(StatementItem::Continue { label }, line))
+    }
+
+    // throw_statement := throw <expression> ;
+    fn throw_statement(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Throw)?;
+        let value = self.expression()?;
+        self.end_statement("Expect ';' after thrown value.")?;
+        Ok(Statement::new(StatementItem::Throw(value), line))
+    }
+
+    // try_statement := try <block> ( catch '(' <id> ')' <block> )? ( finally <block> )?
+    // where at least one of the catch and finally clauses is present
+    fn try_statement(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Try)?;
+        let body = self.block_statements()?;
+        let catch = if match_head!(self, Token::Catch) {
+            self.advance();
+            consume!(self, Token::LeftParen)?;
+            let name = self.identifier()?;
+            consume!(self, Token::RightParen)?;
+            Some((name, self.block_statements()?))
+        } else {
+            None
+        };
+        let finally = if match_head!(self, Token::Finally) {
+            self.advance();
+            Some(self.block_statements()?)
+        } else {
+            None
+        };
+        if catch.is_none() && finally.is_none() {
+            return Err(format!(
+                "Line {}: Expected catch or finally after try block",
+                line
+            ));
+        }
+        Ok(Statement::new(
+            StatementItem::Try {
+                body,
+                catch,
+                finally,
+            },
+            line,
+        ))
+    }
+
+    // match_statement := match '(' <expression> ')' { <match_arm>* }
+    // match_arm := case <match_pattern> ( if <expression> )? => <statement> ,?
+    // where the `;` ending a simple <statement> can be left out before the `,` or `}`
+    fn match_statement(&mut self) -> ParseResult<Statement> {
+        let line = consume!(self, Token::Match)?;
+        consume!(self, Token::LeftParen)?;
+        let subject = self.expression()?;
+        consume!(self, Token::RightParen)?;
+        consume!(self, Token::LeftBrace)?;
+        let mut arms: Vec<MatchArm> = vec![];
+        while !match_head!(self, Token::RightBrace) && !self.is_at_end() {
+            let arm_line = consume!(self, Token::Case)?;
+            if arms
+                .iter()
+                .any(|arm| arm.pattern.is_irrefutable() && arm.guard.is_none())
+            {
+                self.warnings.push(format!(
+                    "Line {}: Warning: unreachable match arm; an earlier arm matches every value",
+                    arm_line
+                ));
+            }
+            let pattern = self.match_pattern()?;
+            if let Some(duplicate) = first_duplicate(&pattern.names()) {
+                return Err(format!(
+                    "Line {}: '{}' is bound more than once in the same pattern",
+                    arm_line, duplicate
+                ));
+            }
+            let guard = if match_head!(self, Token::If) {
+                self.advance();
+                // so that a guard like `if (x) =>` is not taken for an arrow function
+                self.guard_arrow = self.next_arrow();
+                let guard = self.expression();
+                self.guard_arrow = None;
+                Some(guard?)
+            } else {
+                None
+            };
+            consume!(self, Token::Arrow)?;
+            self.arm_body = true;
+            let body = self.statement();
+            self.arm_body = false;
+            let body = Box::new(body?);
+            if match_head!(self, Token::Comma) {
+                self.advance();
+            }
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+        }
+        consume!(self, Token::RightBrace)?;
+        Ok(Statement::new(StatementItem::Match { subject, arms }, line))
+    }
+
+    // match_pattern := _ | <id> | -? <literal>
+    //                | [ ( <match_pattern> ( , <match_pattern> )* )? ( , ... <id> )? ]
+    //                | <id> . <id> ( '(' ( <match_pattern> ( , <match_pattern> )* )? ')' )?
+    fn match_pattern(&mut self) -> ParseResult<MatchPattern> {
+        let token_meta = self
+            .peek()
+            .ok_or_else(|| String::from("EOF: Expected a pattern but got EOF"))?;
+        let line = token_meta.line();
+        match token_meta.item_clone() {
+            Token::Literal(Literal::Identifier(enumeration))
+                if matches!(self.peek_next().map(|t| t.item()), Some(Token::Dot)) =>
+            {
+                self.advance();
+                self.advance();
+                let variant = self.identifier()?;
+                let fields = if match_head!(self, Token::LeftParen) {
+                    self.advance();
+                    let mut fields = vec![];
+                    while !match_head!(self, Token::RightParen) {
+                        fields.push(self.match_pattern()?);
+                        if match_head!(self, Token::Comma) {
+                            self.advance();
+                        } else {
+                            break;
+                        }
+                    }
+                    consume!(self, Token::RightParen)?;
+                    Some(fields)
+                } else {
+                    None
+                };
+                Ok(MatchPattern::Variant {
+                    enumeration,
+                    variant,
+                    fields,
+                })
+            }
+            Token::Literal(Literal::Identifier(name)) => {
+                self.advance();
+                Ok(if name == "_" {
+                    MatchPattern::Wildcard
+                } else {
+                    MatchPattern::Binding(name)
+                })
+            }
+            Token::Literal(value) => {
+                self.advance();
+                Ok(MatchPattern::Literal(value))
+            }
+            Token::Minus => {
+                self.advance();
+                let value = match self.peek().map(|t| t.item_clone()) {
+                    Some(Token::Literal(literal @ Literal::Integer(_)))
+                    | Some(Token::Literal(literal @ Literal::BigInteger(_))) => {
+                        integer::negate(&literal).unwrap()
+                    }
+                    Some(Token::Literal(Literal::Number(n))) => Literal::Number(-n),
+                    _ => {
+                        return Err(format!(
+                            "Line {}: Expected a number after '-' in pattern",
+                            line
+                        ))
+                    }
+                };
+                self.advance();
+                Ok(MatchPattern::Literal(value))
+            }
+            Token::LeftBracket => {
+                self.advance();
+                let mut elements = vec![];
+                let mut rest = None;
+                while !match_head!(self, Token::RightBracket) {
+                    if match_head!(self, Token::Ellipsis) {
+                        self.advance();
+                        rest = Some(self.identifier()?);
+                        break;
+                    }
+                    elements.push(self.match_pattern()?);
+                    if match_head!(self, Token::Comma) {
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                consume!(self, Token::RightBracket)?;
+                Ok(MatchPattern::List { elements, rest })
+            }
+            t => Err(format!("Line {}: Expected a pattern but got {}", line, t)),
+        }
+    }
+
+    /// the position of the next `=>` that is not inside brackets
+    fn next_arrow(&self) -> Option<usize> {
+        let mut depth = 0;
+        for (offset, token_meta) in self.tokens[self.current..].iter().enumerate() {
+            match token_meta.item() {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+                Token::Arrow if depth == 0 => return Some(self.current + offset),
+                _ => (),
+            }
+        }
+        None
+    }
+
+    fn loop_label(&mut self) -> ParseResult<Option<String>> {
+        if match_head!(self, Token::Semicolon) {
+            Ok(None)
+        } else {
+            self.identifier().map(Some)
+        }
+    }
+
     // while_statement := while '(' <expression> ')' <statement>
-    fn while_statement(&mut self) -> ParseResult<Statement> {
+    fn while_statement(&mut self, label: Option<String>) -> ParseResult<Statement> {
         let line = consume!(self, Token::While)?;
-        consume!(self, Token::LeftParen)?;
+        consume!(self, Token::LeftParen, "Expect '(' after 'while'.")?;
         let test = self.expression()?;
-        consume!(self, Token::RightParen)?;
+        consume!(self, Token::RightParen, "Expect ')' after condition.")?;
         let body = Box::new(self.statement()?);
 
         Ok(Statement::new(
-            StatementItem::WhileStatement { test, body },
+            StatementItem::WhileStatement {
+                test,
+                body,
+                increment: None,
+                label,
+            },
             line,
         ))
     }
 
     // for_statement := for '(' ( <expression> | <var_declaration> )?; <expression>?; <expression>? ')' <statement>
-    fn for_statement(&mut self) -> ParseResult<Statement> {
+    //                | for '(' <pattern> in <expression> ')' <statement>
+    fn for_statement(&mut self, label: Option<String>) -> ParseResult<Statement> {
+        if self.is_for_in() {
+            return self.for_in_statement(label);
+        }
         // convert 
         // `for (<init>; <test>; <update>) <body>`
         // into the equivalent:
@@ -223,17 +1015,18 @@ impl Parser {
         //     <update>
         // }
         // ```
+        // where <update> is kept separate from <body> so that it still runs on `continue`
 
         let line = consume!(self, Token::For)?;
-        consume!(self, Token::LeftParen)?;
+        consume!(self, Token::LeftParen, "Expect '(' after 'for'.")?;
         let initializer = if match_head!(self, Token::Semicolon) {
+            self.advance();
             None
         } else if match_head!(self, Token::Var) {
             Some(self.var_declaration()?)
         } else {
             Some(self.expression_statement()?)
         };
-        consume!(self, Token::Semicolon);
 
         let condition = if !match_head!(self, Token::Semicolon) {
             self.expression()?
@@ -246,34 +1039,16 @@ impl Parser {
                 line,
             )
         };
-        consume!(self, Token::Semicolon);
+        consume!(self, Token::Semicolon, "Expect ';' after loop condition.")?;
 
         let increment = if !match_head!(self, Token::RightParen) {
-            let expression = self.expression()?;
-            let expression_line = expression.line();
-            Some(Statement::new(
-                StatementItem::ExpressionStatement(expression),
-                expression_line,
-            ))
+            Some(self.expression()?)
         } else {
             None
         };
-        consume!(self, Token::RightParen);
-
-        let for_body = self.statement()?;
-        // if the for loop has an increment/update section, 
-        // put it at the end of the while loop body
-        let body = Box::new(if let Some(increment) = increment {
-            let line = increment.line();
-            Statement::new(
-                StatementItem::Block {
-                    statements: vec![for_body, increment],
-                },
-                line,
-            )
-        } else {
-            for_body
-        });
+        consume!(self, Token::RightParen, "Expect ')' after for clauses.")?;
+
+        let body = Box::new(self.statement()?);
         let mut statements = vec![];
         if let Some(initializer) = initializer {
             statements.push(initializer);
@@ -283,18 +1058,64 @@ impl Parser {
             StatementItem::WhileStatement {
                 test: condition,
                 body,
+                increment,
+                label,
             },
             line,
         ));
         Ok(Statement::new(StatementItem::Block { statements }, line))
     }
 
+    fn for_in_statement(&mut self, label: Option<String>) -> ParseResult<Statement> {
+        let line = consume!(self, Token::For)?;
+        consume!(self, Token::LeftParen)?;
+        let pattern = self.pattern()?;
+        if let Some(duplicate) = first_duplicate(&pattern.names()) {
+            return Err(format!(
+                "Line {}: '{}' is bound more than once in the same loop variable",
+                line, duplicate
+            ));
+        }
+        consume!(self, Token::In)?;
+        let iterable = self.expression()?;
+        consume!(self, Token::RightParen)?;
+        let body = Box::new(self.statement()?);
+        Ok(Statement::new(
+            StatementItem::ForIn {
+                pattern,
+                iterable,
+                body,
+                label,
+            },
+            line,
+        ))
+    }
+
+    /// check whether the `for` at the head has an `in` before the end of its first clause
+    fn is_for_in(&self) -> bool {
+        let mut depth = 0;
+        // skip the `for` and its opening paren
+        for token_meta in self.tokens.iter().skip(self.current + 2) {
+            match token_meta.item() {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParen | Token::RightBracket | Token::RightBrace if depth == 0 => {
+                    return false
+                }
+                Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+                Token::In if depth == 0 => return true,
+                Token::Semicolon | Token::EOF => return false,
+                _ => (),
+            }
+        }
+        false
+    }
+
     // if_statement := if '(' <expression> ')' statement ( <else> statement )?
     fn if_statement(&mut self) -> ParseResult<Statement> {
         let start_line = consume!(self, Token::If)?;
-        consume!(self, Token::LeftParen)?;
+        consume!(self, Token::LeftParen, "Expect '(' after 'if'.")?;
         let test = self.expression()?;
-        consume!(self, Token::RightParen)?;
+        consume!(self, Token::RightParen, "Expect ')' after if condition.")?;
         let when_true = Box::new(self.statement()?);
         let when_false = if match_head!(self, Token::Else) {
             self.advance();
@@ -315,23 +1136,32 @@ impl Parser {
 
     // block := { <declaration>* }
     fn block(&mut self) -> ParseResult<Statement> {
-        let start_line = consume!(self, Token::LeftBrace)?;
-        let mut statements = vec![];
-        while !match_head!(self, Token::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
-        }
-        consume!(self, Token::RightBrace)?;
+        let start_line = self.peek().map(|t| t.line()).unwrap_or_default();
+        let statements = self.block_statements()?;
         Ok(Statement::new(
             StatementItem::Block { statements },
             start_line,
         ))
     }
 
+    fn block_statements(&mut self) -> ParseResult<Vec<Statement>> {
+        consume!(self, Token::LeftBrace)?;
+        // statements in a block inside an arm's body need their `;` as usual
+        let arm_body = std::mem::replace(&mut self.arm_body, false);
+        let mut statements = vec![];
+        while !match_head!(self, Token::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        self.arm_body = arm_body;
+        consume!(self, Token::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
     // print_statement := print <expression>
     fn print_statement(&mut self) -> ParseResult<Statement> {
         consume!(self, Token::Print)?;
         let expression = self.expression()?;
-        consume!(self, Token::Semicolon)?;
+        self.end_statement("Expect ';' after value.")?;
         let line = expression.line();
         Ok(Statement::new(
             StatementItem::PrintStatement(expression),
@@ -339,10 +1169,26 @@ impl Parser {
         ))
     }
 
+    // whether the next token ends a simple statement; in a match arm's body that can be
+    // the `,` or `}` after the arm instead of a `;`
+    fn at_statement_end(&self) -> bool {
+        match_head!(self, Token::Semicolon)
+            || (self.arm_body
+                && (match_head!(self, Token::Comma) || match_head!(self, Token::RightBrace)))
+    }
+
+    fn end_statement(&mut self, book_message: &str) -> ParseResult<()> {
+        if !match_head!(self, Token::Semicolon) && self.at_statement_end() {
+            return Ok(());
+        }
+        consume!(self, Token::Semicolon, book_message)?;
+        Ok(())
+    }
+
     // expression_statement := <expression>;
     fn expression_statement(&mut self) -> ParseResult<Statement> {
         let expression = self.expression()?;
-        consume!(self, Token::Semicolon)?;
+        self.end_statement("Expect ';' after expression.")?;
         let line = expression.line();
         Ok(Statement::new(
             StatementItem::ExpressionStatement(expression),
@@ -355,26 +1201,96 @@ impl Parser {
         self.assigment()
     }
 
-    // assignment := <id> = <assignment> | <ternary>
+    // assignment := ( <call> . )? <id> = <assignment> | <call> [ <expression> ] = <assignment>
+    //             | [ <target> ( , <target> )* ] = <assignment>
+    //             | <target> ( += | -= | *= | /= ) <assignment> | yield <assignment>? | <ternary>
     fn assigment(&mut self) -> ParseResult<Expression> {
+        if match_head!(self, Token::Yield) {
+            let line = consume!(self, Token::Yield)?;
+            // a bare yield is followed by whatever ends the expression it is part of
+            let ends_expression = match_head!(self, Token::Semicolon)
+                || match_head!(self, Token::RightParen)
+                || match_head!(self, Token::RightBracket)
+                || match_head!(self, Token::RightBrace)
+                || match_head!(self, Token::Comma);
+            let value = if ends_expression {
+                None
+            } else {
+                Some(Box::new(self.assigment()?))
+            };
+            return Ok(Expression::new(ExpressionItem::Yield(value), line));
+        }
         let lhs = self.ternary()?;
-        if match_head!(self, Token::Equal) {
+        let compound_operator = self.peek().and_then(|t| match t.item() {
+            Token::PlusEqual => Some(Token::Plus),
+            Token::MinusEqual => Some(Token::Minus),
+            Token::StarEqual => Some(Token::Star),
+            Token::SlashEqual => Some(Token::Slash),
+            _ => None,
+        });
+        if let Some(operator) = compound_operator {
             let line = self.peek().unwrap().line();
             self.advance();
             let rhs = self.assigment()?;
-            if let ExpressionItem::Variable { name } = lhs.item() {
-                Ok(Expression::new(
+            if !is_assignable(&lhs) {
+                return Err(format!(
+                    "Line {}: Invalid assignment target: {:?}",
+                    line, lhs
+                ));
+            }
+            let target_line = lhs.line();
+            Ok(Expression::new(
+                ExpressionItem::CompoundAssignment {
+                    target: Box::new(lhs),
+                    operator,
+                    value: Box::new(rhs),
+                },
+                target_line,
+            ))
+        } else if match_head!(self, Token::Equal) {
+            let line = self.peek().unwrap().line();
+            let equals = self.current;
+            self.advance();
+            let rhs = self.assigment()?;
+            match lhs.item_clone() {
+                ExpressionItem::Variable { name, .. } => Ok(Expression::new(
                     ExpressionItem::Assignment {
-                        name: name.clone(),
+                        name,
                         value: Box::new(rhs),
+                        depth: None,
                     },
                     lhs.line(),
-                ))
-            } else {
-                Err(format!(
-                    "Line {}: Invalid assignment target: {:?}",
-                    line, lhs
-                ))
+                )),
+                ExpressionItem::Get { object, name } => Ok(Expression::new(
+                    ExpressionItem::Set {
+                        object,
+                        name,
+                        value: Box::new(rhs),
+                    },
+                    lhs.line(),
+                )),
+                ExpressionItem::Index { object, index } => Ok(Expression::new(
+                    ExpressionItem::IndexSet {
+                        object,
+                        index,
+                        value: Box::new(rhs),
+                    },
+                    lhs.line(),
+                )),
+                ExpressionItem::List { elements } if elements.iter().all(is_list_target) => {
+                    Ok(Expression::new(
+                        ExpressionItem::ListAssignment {
+                            targets: elements,
+                            value: Box::new(rhs),
+                        },
+                        lhs.line(),
+                    ))
+                }
+                _ => {
+                    // the reference implementation reports the `=` rather than where parsing stopped
+                    self.error_token = Some(equals);
+                    Err(format!("Line {}: Invalid assignment target: {:?}", line, lhs))
+                }
             }
         } else {
             Ok(lhs)
@@ -385,7 +1301,7 @@ impl Parser {
         let first = self.logical_or()?;
         // try to parse a ternary operator
         match self.peek() {
-            None => return Ok(first),
+            None => Ok(first),
             Some(token_meta) => {
                 let line = token_meta.line();
                 let token = token_meta.item_clone();
@@ -411,7 +1327,7 @@ impl Parser {
                             Err(format!("Line {}: Expected ':' but got {}", line, token))
                         }
                     }
-                    _ => return Ok(first),
+                    _ => Ok(first),
                 }
             }
         }
@@ -431,20 +1347,63 @@ impl Parser {
         Token::BangEqual
     );
 
-    // equality := <addition> ( (> | < | >= | <=) <addition>)*
+    // comparison := <range> ( (> | < | >= | <=) <range>)*
     binary_expression_parser!(
         comparison,
-        Self::addition,
+        Self::range,
         Token::Greater,
         Token::GreaterEqual,
         Token::Lesser,
         Token::LesserEqual
     );
+    // range := <bitwise_or> ( (.. | ..=) <bitwise_or> )?
+    fn range(&mut self) -> ParseResult<Expression> {
+        let start = self.bitwise_or()?;
+        let inclusive = if match_head!(self, Token::DotDot) {
+            false
+        } else if match_head!(self, Token::DotDotEqual) {
+            true
+        } else {
+            return Ok(start);
+        };
+        self.advance();
+        let end = self.bitwise_or()?;
+        let line = start.line();
+        Ok(Expression::new(
+            ExpressionItem::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+                inclusive,
+            },
+            line,
+        ))
+    }
+
+    // bitwise_or := <bitwise_xor> ( | <bitwise_xor>)*
+    binary_expression_parser!(bitwise_or, Self::bitwise_xor, Token::Pipe,);
+    // bitwise_xor := <bitwise_and> ( ^ <bitwise_and>)*
+    binary_expression_parser!(bitwise_xor, Self::bitwise_and, Token::Caret,);
+    // bitwise_and := <shift> ( & <shift>)*
+    binary_expression_parser!(bitwise_and, Self::shift, Token::Ampersand,);
+    // shift := <addition> ( (<< | >>) <addition>)*
+    binary_expression_parser!(
+        shift,
+        Self::addition,
+        Token::LesserLesser,
+        Token::GreaterGreater
+    );
     // addition := <multiplication> ( (+ | -) <multiplication>)*
     binary_expression_parser!(addition, Self::multiplication, Token::Plus, Token::Minus);
-    // multiplication := <unary> ( (* | /) <comparison>)*
-    binary_expression_parser!(multiplication, Self::unary, Token::Slash, Token::Star);
-    // unary := (+ | - | !)? <primary>
+    // multiplication := <unary> ( (* | / | ~/ | %) <unary>)*
+    binary_expression_parser!(
+        multiplication,
+        Self::unary,
+        Token::Slash,
+        Token::Star,
+        Token::TildeSlash,
+        Token::Percent
+    );
+    // unary := (+ | - | ! | ~) <unary> | (++ | --) <unary> | <power>
     fn unary(&mut self) -> ParseResult<Expression> {
         let token_meta = self.peek().ok_or(String::from(
             "EOF: No more tokens while parsing a unary expression",
@@ -452,7 +1411,16 @@ impl Parser {
         let line = token_meta.line();
         let token = token_meta.item_clone();
         match token {
-            Token::Plus | Token::Minus => {
+            Token::PlusPlus | Token::MinusMinus => {
+                self.advance();
+                let target = self.unary()?;
+                increment(target, &token, true, line)
+            }
+            Token::Plus if self.dialect.is_book() => Err(format!(
+                "Line {}: Failed to parse {}; expected expression",
+                line, token
+            )),
+            Token::Plus | Token::Minus | Token::Bang | Token::Tilde => {
                 self.advance();
                 let right = Box::new(self.unary()?);
                 Ok(Expression::new(
@@ -463,6 +1431,9 @@ impl Parser {
                     line,
                 ))
             }
+            t if t.is_operator() && self.dialect.is_book() => {
+                Err(self.error_here(&format!("Failed to parse {}; expected expression", t)))
+            }
             t if t.is_operator() => {
                 self.advance();
                 self.primary().ok(); // try to consume the right operand
@@ -471,11 +1442,221 @@ impl Parser {
                     line, t
                 ))
             }
-            _ => self.primary(),
+            _ => self.power(),
+        }
+    }
+
+    // power := <postfix> ( ** <unary> )?
+    // the right operand is a unary so that `**` is right associative and `2 ** -1` parses
+    fn power(&mut self) -> ParseResult<Expression> {
+        let base = self.postfix()?;
+        if match_head!(self, Token::StarStar) {
+            self.advance();
+            let exponent = self.unary()?;
+            let line = base.line();
+            Ok(Expression::new(
+                ExpressionItem::Binary {
+                    left: Box::new(base),
+                    operator: Token::StarStar,
+                    right: Box::new(exponent),
+                },
+                line,
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    // postfix := <call> ( ++ | -- )?
+    fn postfix(&mut self) -> ParseResult<Expression> {
+        let target = self.call()?;
+        match self.peek().map(|t| (t.line(), t.item_clone())) {
+            Some((line, token @ Token::PlusPlus)) | Some((line, token @ Token::MinusMinus)) => {
+                self.advance();
+                increment(target, &token, false, line)
+            }
+            _ => Ok(target),
+        }
+    }
+
+    // call := <primary> ( '(' <arguments>? ')' | . <id> | [ <expression> ] | [ <expression>? : <expression>? ] )*
+    // arguments := <expression> ( , <expression> )*
+    fn call(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.primary()?;
+        loop {
+            if match_head!(self, Token::LeftParen) {
+                let line = consume!(self, Token::LeftParen)?;
+                let (arguments, named) = self.arguments()?;
+                expr = Expression::new(
+                    ExpressionItem::Call {
+                        callee: Box::new(expr),
+                        arguments,
+                        named,
+                    },
+                    line,
+                );
+            } else if match_head!(self, Token::Dot) {
+                let line = consume!(self, Token::Dot)?;
+                let name = self.expect_identifier("Expect property name after '.'.")?;
+                expr = Expression::new(
+                    ExpressionItem::Get {
+                        object: Box::new(expr),
+                        name,
+                    },
+                    line,
+                );
+            } else if match_head!(self, Token::LeftBracket) {
+                let line = consume!(self, Token::LeftBracket)?;
+                let start = if match_head!(self, Token::Colon) {
+                    None
+                } else {
+                    Some(Box::new(self.expression()?))
+                };
+                let item = match start {
+                    Some(index) if !match_head!(self, Token::Colon) => ExpressionItem::Index {
+                        object: Box::new(expr),
+                        index,
+                    },
+                    start => {
+                        consume!(self, Token::Colon)?;
+                        let end = if match_head!(self, Token::RightBracket) {
+                            None
+                        } else {
+                            Some(Box::new(self.expression()?))
+                        };
+                        ExpressionItem::Slice {
+                            object: Box::new(expr),
+                            start,
+                            end,
+                        }
+                    }
+                };
+                consume!(self, Token::RightBracket)?;
+                expr = Expression::new(item, line);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    // parses the arguments of a call, including the closing ')'.
+    // Named arguments, `<id> : <expression>`, come after all the positional ones
+    fn arguments(&mut self) -> ParseResult<Arguments> {
+        let mut arguments = vec![];
+        let mut named: Vec<(String, Expression)> = vec![];
+        while !match_head!(self, Token::RightParen) {
+            if self.is_label() {
+                let line = self.peek().unwrap().line();
+                let name = self.identifier()?;
+                consume!(self, Token::Colon)?;
+                if named.iter().any(|(n, _)| *n == name) {
+                    return Err(format!(
+                        "Line {}: Argument '{}' is given more than once",
+                        line, name
+                    ));
+                }
+                named.push((name, self.expression()?));
+            } else if named.is_empty() {
+                arguments.push(self.expression()?);
+            } else {
+                let line = self.peek().unwrap().line();
+                return Err(format!(
+                    "Line {}: Positional arguments cannot follow named arguments",
+                    line
+                ));
+            }
+            if match_head!(self, Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        consume!(self, Token::RightParen, "Expect ')' after arguments.")?;
+        Ok((arguments, named))
+    }
+
+    // list := [ ( <expression> ( , <expression> )* ,? )? ]
+    fn list(&mut self) -> ParseResult<Expression> {
+        let line = consume!(self, Token::LeftBracket)?;
+        let mut elements = vec![];
+        while !match_head!(self, Token::RightBracket) {
+            elements.push(self.expression()?);
+            if match_head!(self, Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        consume!(self, Token::RightBracket)?;
+        Ok(Expression::new(ExpressionItem::List { elements }, line))
+    }
+
+    // map := { ( <expression> : <expression> ( , <expression> : <expression> )* ,? )? }
+    fn map(&mut self) -> ParseResult<Expression> {
+        let line = consume!(self, Token::LeftBrace)?;
+        let mut entries = vec![];
+        while !match_head!(self, Token::RightBrace) {
+            let key = self.expression()?;
+            consume!(self, Token::Colon)?;
+            let value = self.expression()?;
+            entries.push((key, value));
+            if match_head!(self, Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
         }
+        consume!(self, Token::RightBrace)?;
+        Ok(Expression::new(ExpressionItem::Map { entries }, line))
     }
 
-    // primary := <literal> | <id> | ( <expression> )
+    // parses each `${...}` in an interpolated string as a standalone expression
+    fn interpolation(&mut self, parts: Vec<StringPart>, line: usize) -> ParseResult<Expression> {
+        let mut expressions = vec![];
+        for part in parts {
+            match part {
+                StringPart::Literal(s) if s.is_empty() => (),
+                StringPart::Literal(s) => expressions.push(Expression::new(
+                    ExpressionItem::Literal {
+                        value: Literal::StringT(s),
+                    },
+                    line,
+                )),
+                StringPart::Interpolation(tokens) if tokens.is_empty() => {
+                    return Err(format!("Line {}: Empty interpolation in string", line))
+                }
+                StringPart::Interpolation(tokens) => {
+                    let last_line = tokens.last().map(|t| t.line()).unwrap_or(line);
+                    let mut parser = Parser::new(tokens);
+                    // the interpolation's tokens end at its '}', not at the end of the file
+                    expressions.push(parser.expression().map_err(|e| {
+                        if e.starts_with("EOF") {
+                            format!(
+                                "Line {}: Incomplete expression in string interpolation",
+                                last_line
+                            )
+                        } else {
+                            e
+                        }
+                    })?);
+                    if let Some(extra) = parser.peek() {
+                        return Err(format!(
+                            "Line {}: Unexpected {} in string interpolation",
+                            extra.line(),
+                            extra.item()
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(Expression::new(
+            ExpressionItem::Interpolation { parts: expressions },
+            line,
+        ))
+    }
+
+    // primary := <literal> | <id> | <list> | <map> | <lambda> | this | super . <id> | ( <expression> )
     fn primary(&mut self) -> ParseResult<Expression> {
         let token_meta = self.peek().ok_or(String::from(
             "EOF: No more tokens while parsing a primary expression",
@@ -486,7 +1667,7 @@ impl Parser {
             self.advance();
             return Ok(Expression::new(
                 if let Literal::Identifier(name) = l {
-                    ExpressionItem::Variable { name }
+                    ExpressionItem::Variable { name, depth: None }
                 } else {
                     ExpressionItem::Literal { value: l }
                 },
@@ -495,15 +1676,39 @@ impl Parser {
         }
 
         match token {
+            Token::InterpolatedString(parts) => {
+                self.advance();
+                self.interpolation(parts, line_number)
+            }
+            Token::LeftBracket => self.list(),
+            Token::LeftBrace if !self.dialect.is_book() => self.map(),
+            Token::Fun if !self.dialect.is_book() => self.lambda(),
+            Token::LeftParen if self.is_arrow_function() => self.lambda(),
+            Token::This => {
+                self.advance();
+                Ok(Expression::new(
+                    ExpressionItem::This { depth: None },
+                    line_number,
+                ))
+            }
+            Token::Super => {
+                self.advance();
+                consume!(self, Token::Dot, "Expect '.' after 'super'.")?;
+                let method = self.expect_identifier("Expect superclass method name.")?;
+                Ok(Expression::new(
+                    ExpressionItem::Super {
+                        method,
+                        depth: None,
+                    },
+                    line_number,
+                ))
+            }
             Token::LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
                 let _next = self
                     .peek()
-                    .filter(|next_token_meta| match next_token_meta.item() {
-                        Token::RightParen => true,
-                        _ => false,
-                    })
+                    .filter(|next_token_meta| matches!(next_token_meta.item(), Token::RightParen))
                     .ok_or(format!(
                         "Line {}: Expected closing parenthesis for expression",
                         line_number
@@ -511,10 +1716,10 @@ impl Parser {
                 self.advance();
                 Ok(expr)
             }
-            t => Err(String::from(format!(
+            t => Err(format!(
                 "Line {}: Failed to parse {}; expected expression",
                 line_number, t
-            ))),
+            )),
         }
     }
 
@@ -522,7 +1727,7 @@ impl Parser {
         self.current += 1
     }
 
-    fn peek<'a>(&'a self) -> Option<&'a TokenMeta> {
+    fn peek(&self) -> Option<&TokenMeta> {
         if self.is_at_end() {
             None
         } else {
@@ -530,7 +1735,65 @@ impl Parser {
         }
     }
 
+    fn peek_next(&self) -> Option<&TokenMeta> {
+        self.tokens.get(self.current + 1)
+    }
+
     pub fn reset(&mut self) {
         self.current = 0;
+        self.error_token = None;
     }
 }
+
+/// Whether an expression can appear on the left of an assignment
+fn is_assignable(expression: &Expression) -> bool {
+    matches!(
+        expression.item(),
+        ExpressionItem::Variable { .. } | ExpressionItem::Get { .. } | ExpressionItem::Index { .. }
+    )
+}
+
+/// Whether an expression can be assigned to inside a list on the left of an assignment
+fn is_list_target(expression: &Expression) -> bool {
+    match expression.item() {
+        ExpressionItem::List { elements } => elements.iter().all(is_list_target),
+        _ => is_assignable(expression),
+    }
+}
+
+// build the increment or decrement of `target` for a `++` or `--` token
+fn increment(
+    target: Expression,
+    token: &Token,
+    prefix: bool,
+    line: usize,
+) -> ParseResult<Expression> {
+    if !is_assignable(&target) {
+        return Err(format!(
+            "Line {}: Invalid {} target: {:?}",
+            line, token, target
+        ));
+    }
+    let operator = match token {
+        Token::PlusPlus => Token::Plus,
+        _ => Token::Minus,
+    };
+    let target_line = target.line();
+    Ok(Expression::new(
+        ExpressionItem::Increment {
+            target: Box::new(target),
+            operator,
+            prefix,
+        },
+        target_line,
+    ))
+}
+
+/// The first name that appears more than once, if any
+fn first_duplicate<'a>(names: &[&'a str]) -> Option<&'a str> {
+    names
+        .iter()
+        .enumerate()
+        .find_map(|(i, name)| names[..i].iter().find(|earlier| *earlier == name))
+        .copied()
+}
@@ -0,0 +1,641 @@
+use crate::data::ast::{
+    Expression, ExpressionItem, FunctionDefinition, MatchPattern, Statement, StatementItem,
+};
+use crate::data::enums::LoxEnum;
+use crate::dialect::{self, Dialect};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Generator,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// What the resolver knows about a local name
+#[derive(Clone, Copy)]
+struct Binding {
+    // whether its initializer has finished resolving
+    defined: bool,
+    constant: bool,
+}
+
+/// Static analysis pass run between parsing and interpreting.
+/// Binds every variable use to the number of scopes between it and its declaration,
+/// and reports errors that can be detected without running the program
+pub struct Resolver {
+    scopes: Vec<HashMap<String, Binding>>,
+    // the top-level names declared with `const` so far
+    global_constants: HashSet<String>,
+    // the enums declared so far by name, used to check the variant patterns of match arms
+    enums: HashMap<String, Rc<LoxEnum>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // the labels of the loops enclosing the current statement, innermost last
+    loops: Vec<Option<String>>,
+    errors: Vec<String>,
+    dialect: Dialect,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: vec![],
+            global_constants: HashSet::new(),
+            enums: HashMap::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loops: vec![],
+            errors: vec![],
+            dialect: Dialect::default(),
+        }
+    }
+
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    pub fn resolve(&mut self, statements: &mut [Statement]) -> Result<(), Vec<String>> {
+        self.resolve_statements(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &mut [Statement]) {
+        for statement in statements.iter_mut() {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &mut Statement) {
+        let line = statement.line();
+        match statement.item_mut() {
+            StatementItem::ExpressionStatement(expr) | StatementItem::PrintStatement(expr) => {
+                self.resolve_expression(expr)
+            }
+            StatementItem::Declaration {
+                pattern,
+                initializer,
+                constant,
+                ..
+            } => {
+                let names = pattern.names();
+                for name in names.iter() {
+                    self.declare(name, line);
+                }
+                self.resolve_expression(initializer);
+                for name in names.iter() {
+                    if *constant {
+                        self.define_constant(name);
+                    } else {
+                        self.define(name);
+                    }
+                }
+            }
+            StatementItem::Block { statements } => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            StatementItem::IfStatement {
+                test,
+                when_true,
+                when_false,
+            } => {
+                self.resolve_expression(test);
+                self.resolve_statement(when_true);
+                if let Some(when_false) = when_false {
+                    self.resolve_statement(when_false);
+                }
+            }
+            StatementItem::WhileStatement {
+                test,
+                body,
+                increment,
+                label,
+            } => {
+                self.resolve_expression(test);
+                self.loops.push(label.clone());
+                self.resolve_statement(body);
+                self.loops.pop();
+                if let Some(increment) = increment {
+                    self.resolve_expression(increment);
+                }
+            }
+            StatementItem::ForIn {
+                pattern,
+                iterable,
+                body,
+                label,
+            } => {
+                self.resolve_expression(iterable);
+                self.loops.push(label.clone());
+                self.begin_scope();
+                for name in pattern.names() {
+                    self.declare(name, line);
+                    self.define(name);
+                }
+                self.resolve_statement(body);
+                self.end_scope();
+                self.loops.pop();
+            }
+            StatementItem::Break { label } => self.resolve_loop_jump("break", label, line),
+            StatementItem::Continue { label } => {
+                self.resolve_loop_jump("continue", label, line)
+            }
+            StatementItem::FunctionDeclaration(definition) => {
+                // define eagerly so that the function can refer to itself
+                self.declare(&definition.name, line);
+                self.define(&definition.name);
+                self.resolve_function(Rc::make_mut(definition), FunctionType::Function, line);
+            }
+            StatementItem::ReturnStatement(value) => {
+                if self.current_function == FunctionType::None {
+                    self.error(line, Some("return"), "Cannot return from top-level code");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(
+                            line,
+                            Some("return"),
+                            "Cannot return a value from an initializer",
+                        );
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            StatementItem::Throw(value) => self.resolve_expression(value),
+            StatementItem::Import { name, .. } => {
+                self.declare(name, line);
+                self.define(name);
+            }
+            StatementItem::Export(declaration) => {
+                if !self.scopes.is_empty() {
+                    self.error(line, None, "Only top-level declarations can be exported");
+                }
+                self.resolve_statement(declaration);
+            }
+            StatementItem::Match { subject, arms } => {
+                self.resolve_expression(subject);
+                for arm in arms.iter_mut() {
+                    self.check_variant_pattern(&arm.pattern, line);
+                    self.begin_scope();
+                    for name in arm.pattern.names() {
+                        self.declare(name, line);
+                        self.define(name);
+                    }
+                    if let Some(guard) = &mut arm.guard {
+                        self.resolve_expression(guard);
+                    }
+                    self.resolve_statement(&mut arm.body);
+                    self.end_scope();
+                }
+            }
+            StatementItem::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.begin_scope();
+                self.resolve_statements(body);
+                self.end_scope();
+                if let Some((name, handler)) = catch {
+                    self.begin_scope();
+                    self.define(name);
+                    self.resolve_statements(handler);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.resolve_statements(finally);
+                    self.end_scope();
+                }
+            }
+            StatementItem::ClassDeclaration {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+                self.declare(name, line);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let ExpressionItem::Variable {
+                        name: superclass_name,
+                        ..
+                    } = superclass.item()
+                    {
+                        if superclass_name == name {
+                            self.error(
+                                line,
+                                Some(superclass_name),
+                                "A class cannot inherit from itself",
+                            );
+                        }
+                    }
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+                    self.begin_scope();
+                    self.define("super");
+                }
+
+                self.begin_scope();
+                self.define("this");
+                for method in methods.iter_mut() {
+                    let function_type = if method.name == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(Rc::make_mut(method), function_type, line);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.current_class = enclosing_class;
+            }
+            StatementItem::EnumDeclaration(enumeration) => {
+                self.declare(&enumeration.name, line);
+                self.define(&enumeration.name);
+                self.enums
+                    .insert(enumeration.name.clone(), enumeration.clone());
+            }
+        }
+    }
+
+    /// Report variant patterns naming a variant that a known enum does not have,
+    /// or giving it the wrong number of fields
+    fn check_variant_pattern(&mut self, pattern: &MatchPattern, line: usize) {
+        match pattern {
+            MatchPattern::Variant {
+                enumeration,
+                variant,
+                fields,
+            } => {
+                if let Some(declared) = self.enums.get(enumeration) {
+                    match declared.tag(variant).map(|tag| &declared.variants[tag]) {
+                        None => self.error(
+                            line,
+                            None,
+                            &format!("Enum '{}' has no variant '{}'", enumeration, variant),
+                        ),
+                        Some(declared) => match fields {
+                            Some(fields) if fields.len() != declared.fields.len() => {
+                                self.error(
+                                    line,
+                                    None,
+                                    &format!(
+                                        "{}.{} has {} field(s) but the pattern has {}",
+                                        enumeration, variant, declared.fields.len(), fields.len()
+                                    ),
+                                )
+                            }
+                            _ => (),
+                        },
+                    }
+                }
+                for field in fields.iter().flatten() {
+                    self.check_variant_pattern(field, line);
+                }
+            }
+            MatchPattern::List { elements, .. } => {
+                for element in elements {
+                    self.check_variant_pattern(element, line);
+                }
+            }
+            MatchPattern::Wildcard | MatchPattern::Binding(_) | MatchPattern::Literal(_) => (),
+        }
+    }
+
+    fn resolve_loop_jump(&mut self, keyword: &str, label: &Option<String>, line: usize) {
+        match label {
+            None if self.loops.is_empty() => self.error(
+                line,
+                None,
+                &format!("Cannot {} outside of a loop", keyword),
+            ),
+            Some(label) if !self.loops.iter().any(|l| l.as_ref() == Some(label)) => {
+                self.error(
+                    line,
+                    None,
+                    &format!("Cannot {} to '{}'; no enclosing loop has that label", keyword, label),
+                )
+            }
+            _ => (),
+        }
+    }
+
+    fn resolve_function(
+        &mut self,
+        definition: &mut FunctionDefinition,
+        function_type: FunctionType,
+        line: usize,
+    ) {
+        let enclosing_function = self.current_function;
+        self.current_function = if definition.is_generator {
+            FunctionType::Generator
+        } else {
+            function_type
+        };
+        // loops outside a function cannot be broken out of from inside it
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        self.begin_scope();
+        // defaults are evaluated in the function's scope, so they can use earlier parameters
+        for param in definition.params.iter_mut() {
+            if let Some(default) = &mut param.default {
+                self.resolve_expression(default);
+            }
+            self.declare(&param.name, line);
+            self.define(&param.name);
+        }
+        if let Some(rest) = &definition.rest {
+            self.declare(rest, line);
+            self.define(rest);
+        }
+        self.resolve_statements(&mut definition.body);
+        self.end_scope();
+        self.loops = enclosing_loops;
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_expression(&mut self, expression: &mut Expression) {
+        let line = expression.line();
+        match expression.item_mut() {
+            ExpressionItem::Binary { left, right, .. }
+            | ExpressionItem::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            ExpressionItem::Grouping { expression } => self.resolve_expression(expression),
+            ExpressionItem::Literal { .. } => (),
+            ExpressionItem::Unary { operand, .. } => self.resolve_expression(operand),
+            ExpressionItem::Ternary {
+                test,
+                when_true,
+                when_false,
+            } => {
+                self.resolve_expression(test);
+                self.resolve_expression(when_true);
+                self.resolve_expression(when_false);
+            }
+            ExpressionItem::Variable { name, depth } => {
+                if let Some(false) = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(name))
+                    .map(|binding| binding.defined)
+                {
+                    self.error(
+                        line,
+                        Some(name),
+                        &format!("Cannot read local variable '{}' in its own initializer", name),
+                    );
+                }
+                *depth = self.resolve_local(name);
+            }
+            ExpressionItem::Assignment { name, value, depth } => {
+                self.resolve_expression(value);
+                *depth = self.resolve_local(name);
+                self.check_assignable(name, *depth, line);
+            }
+            ExpressionItem::Call {
+                callee,
+                arguments,
+                named,
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments.iter_mut() {
+                    self.resolve_expression(argument);
+                }
+                for (_, argument) in named.iter_mut() {
+                    self.resolve_expression(argument);
+                }
+            }
+            ExpressionItem::Get { object, .. } => self.resolve_expression(object),
+            ExpressionItem::List { elements }
+            | ExpressionItem::Interpolation { parts: elements } => {
+                for element in elements.iter_mut() {
+                    self.resolve_expression(element);
+                }
+            }
+            ExpressionItem::Map { entries } => {
+                for (key, value) in entries.iter_mut() {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            }
+            ExpressionItem::Index { object, index } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+            }
+            ExpressionItem::Slice { object, start, end } => {
+                self.resolve_expression(object);
+                if let Some(start) = start {
+                    self.resolve_expression(start);
+                }
+                if let Some(end) = end {
+                    self.resolve_expression(end);
+                }
+            }
+            ExpressionItem::Range { start, end, .. } => {
+                self.resolve_expression(start);
+                self.resolve_expression(end);
+            }
+            ExpressionItem::IndexSet {
+                object,
+                index,
+                value,
+            } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+                self.resolve_expression(value);
+            }
+            ExpressionItem::CompoundAssignment { target, value, .. } => {
+                self.resolve_expression(target);
+                self.check_target(target);
+                self.resolve_expression(value);
+            }
+            ExpressionItem::Increment { target, .. } => {
+                self.resolve_expression(target);
+                self.check_target(target);
+            }
+            ExpressionItem::ListAssignment { targets, value } => {
+                self.resolve_expression(value);
+                for target in targets.iter_mut() {
+                    self.resolve_expression(target);
+                    self.check_target(target);
+                }
+            }
+            ExpressionItem::Yield(value) => {
+                if self.current_function != FunctionType::Generator {
+                    self.error(line, None, "Cannot yield outside of a generator");
+                }
+                if let Some(value) = value {
+                    self.resolve_expression(value);
+                }
+            }
+            ExpressionItem::Lambda(definition) => {
+                self.resolve_function(Rc::make_mut(definition), FunctionType::Function, line)
+            }
+            ExpressionItem::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            ExpressionItem::This { depth } => {
+                if self.current_class == ClassType::None {
+                    self.error(line, Some("this"), "Cannot use 'this' outside of a class");
+                }
+                *depth = self.resolve_local("this");
+            }
+            ExpressionItem::Super { depth, .. } => {
+                match self.current_class {
+                    ClassType::None => self.error(
+                        line,
+                        Some("super"),
+                        "Cannot use 'super' outside of a class",
+                    ),
+                    ClassType::Class => self.error(
+                        line,
+                        Some("super"),
+                        "Cannot use 'super' in a class with no superclass",
+                    ),
+                    ClassType::Subclass => (),
+                }
+                *depth = self.resolve_local("super");
+            }
+        }
+    }
+
+    /// report an assignment to a variable, or list of variables, that is a constant
+    fn check_target(&mut self, target: &Expression) {
+        match target.item() {
+            ExpressionItem::Variable { name, depth } => {
+                self.check_assignable(name, *depth, target.line())
+            }
+            ExpressionItem::List { elements } => {
+                for element in elements.iter() {
+                    self.check_target(element);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// report an assignment to a name that resolved to a constant
+    fn check_assignable(&mut self, name: &str, depth: Option<usize>, line: usize) {
+        let constant = match depth {
+            Some(depth) => self.scopes[self.scopes.len() - 1 - depth]
+                .get(name)
+                .is_some_and(|binding| binding.constant),
+            None => self.global_constants.contains(name),
+        };
+        if constant {
+            self.error(line, None, &format!("Cannot assign to constant '{}'", name));
+        }
+    }
+
+    /// find how many scopes out a name was declared, or None if it is not a local
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name))
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Record an error; the book dialect reports it as the reference implementation does,
+    /// at the `lexeme` it is about when there is one
+    fn error(&mut self, line: usize, lexeme: Option<&str>, message: &str) {
+        let error = format!("Line {}: {}", line, message);
+        let error = match lexeme {
+            _ if !self.dialect.is_book() => error,
+            Some(lexeme) => dialect::parse_error(line, Some(lexeme), message),
+            None => dialect::compile_error(&error),
+        };
+        self.errors.push(error);
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, line: usize) {
+        let redeclared = self
+            .scopes
+            .last()
+            .is_some_and(|scope| scope.contains_key(name));
+        if redeclared {
+            self.error(
+                line,
+                Some(name),
+                &format!("Variable '{}' is already declared in this scope", name),
+            );
+        }
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                let binding = Binding {
+                    defined: false,
+                    constant: false,
+                };
+                scope.insert(name.to_owned(), binding);
+            }
+            None if self.global_constants.contains(name) => self.error(
+                line,
+                None,
+                &format!("Cannot redeclare constant '{}'", name),
+            ),
+            None => (),
+        }
+    }
+
+    fn define(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let binding = Binding {
+                defined: true,
+                constant: false,
+            };
+            scope.insert(name.to_owned(), binding);
+        }
+    }
+
+    fn define_constant(&mut self, name: &str) {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                let binding = Binding {
+                    defined: true,
+                    constant: true,
+                };
+                scope.insert(name.to_owned(), binding);
+            }
+            None => {
+                self.global_constants.insert(name.to_owned());
+            }
+        }
+    }
+}
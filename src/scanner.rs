@@ -1,28 +1,45 @@
 use crate::errors::{ErrorData, LoxError};
+use crate::data::integer;
 use crate::data::literals::Literal;
-use crate::data::tokens::{Token, TokenMeta};
-use lazy_static::*;
+use crate::data::tokens::{StringPart, Token, TokenMeta};
+use crate::dialect::{Dialect, BOOK_KEYWORDS};
 use std::collections::HashMap;
 
-lazy_static! {
-    static ref RESERVED_WORDS: HashMap<&'static str, Token> = {
+// thread local rather than static, since tokens can carry literals that are not `Sync`
+thread_local! {
+    static RESERVED_WORDS: HashMap<&'static str, Token> = {
         let mut m = HashMap::new();
         m.insert("and", Token::And);
+        m.insert("as", Token::As);
+        m.insert("break", Token::Break);
+        m.insert("case", Token::Case);
+        m.insert("catch", Token::Catch);
         m.insert("class", Token::Class);
+        m.insert("const", Token::Const);
+        m.insert("continue", Token::Continue);
         m.insert("else", Token::Else);
+        m.insert("enum", Token::Enum);
+        m.insert("export", Token::Export);
         m.insert("false", Token::Literal(Literal::False));
+        m.insert("finally", Token::Finally);
         m.insert("fun", Token::Fun);
         m.insert("for", Token::For);
         m.insert("if", Token::If);
+        m.insert("import", Token::Import);
+        m.insert("in", Token::In);
+        m.insert("match", Token::Match);
         m.insert("nil", Token::Literal(Literal::Nil));
         m.insert("or", Token::Or);
         m.insert("print", Token::Print);
         m.insert("return", Token::Return);
         m.insert("super", Token::Super);
         m.insert("this", Token::This);
+        m.insert("throw", Token::Throw);
         m.insert("true", Token::Literal(Literal::True));
+        m.insert("try", Token::Try);
         m.insert("var", Token::Var);
         m.insert("while", Token::While);
+        m.insert("yield", Token::Yield);
         m
     };
 }
@@ -31,6 +48,7 @@ pub struct Scanner {
     src: Vec<char>,
     current: usize,
     line_no: usize,
+    dialect: Dialect,
 }
 
 macro_rules! if_peek_eq {
@@ -50,9 +68,14 @@ impl Scanner {
             src: src.chars().collect(),
             current: 0,
             line_no: 1,
+            dialect: Dialect::default(),
         }
     }
 
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
     pub fn scan_tokens(&mut self) -> Result<Vec<TokenMeta>, Vec<LoxError>> {
         let mut errors = vec![];
         let mut tokens = vec![];
@@ -109,29 +132,76 @@ impl Scanner {
 
     fn next_token(&mut self) -> Option<Result<TokenMeta, LoxError>> {
         use Token::*;
-        let token = match self.next_char()? {
+        let c = self.next_char()?;
+        if self.dialect.is_book() {
+            if let Some(token) = self.next_book_token(c) {
+                return Some(token.map(|token| TokenMeta::new(token, self.line_no)));
+            }
+        }
+        let token = match c {
             '(' => LeftParen,
             ')' => RightParen,
             '{' => LeftBrace,
             '}' => RightBrace,
+            '[' => LeftBracket,
+            ']' => RightBracket,
             ',' => Comma,
+            '.' if self.peek_char() == Some('.') => {
+                self.next_char();
+                if_peek_eq!(self, '.', Ellipsis, if_peek_eq!(self, '=', DotDotEqual, DotDot))
+            }
             '.' => Dot,
-            '-' => Minus,
-            '+' => Plus,
+            '-' => if_peek_eq!(
+                self,
+                '=',
+                MinusEqual,
+                if_peek_eq!(self, '-', MinusMinus, Minus)
+            ),
+            '+' => if_peek_eq!(
+                self,
+                '=',
+                PlusEqual,
+                if_peek_eq!(self, '+', PlusPlus, Plus)
+            ),
             ';' => Semicolon,
-            '*' => Star,
+            '*' => if_peek_eq!(
+                self,
+                '=',
+                StarEqual,
+                if_peek_eq!(self, '*', StarStar, Star)
+            ),
             '?' => Question,
             ':' => Colon,
+            '%' => Percent,
+            '&' => Ampersand,
+            '|' => Pipe,
+            '^' => Caret,
+            '~' => if_peek_eq!(self, '/', TildeSlash, Tilde),
             '!' => if_peek_eq!(self, '=', BangEqual, Bang),
-            '=' => if_peek_eq!(self, '=', EqualEqual, Equal),
-            '<' => if_peek_eq!(self, '=', LesserEqual, Lesser),
-            '>' => if_peek_eq!(self, '=', GreaterEqual, Greater),
+            '=' => if_peek_eq!(
+                self,
+                '=',
+                EqualEqual,
+                if_peek_eq!(self, '>', Arrow, Equal)
+            ),
+            '<' => if_peek_eq!(
+                self,
+                '=',
+                LesserEqual,
+                if_peek_eq!(self, '<', LesserLesser, Lesser)
+            ),
+            '>' => if_peek_eq!(
+                self,
+                '=',
+                GreaterEqual,
+                if_peek_eq!(self, '>', GreaterGreater, Greater)
+            ),
             '/' if self.peek_char() == Some('/') => {
                 // consume until end of line
                 self.current = self.find_next('\n').unwrap_or(self.src.len());
                 return self.next_token();
             }
-            '/' => Slash,
+            '/' => if_peek_eq!(self, '=', SlashEqual, Slash),
             '\r' | ' ' | '\t' => {
                 while let Some(c) = self.peek_char() {
                     if c == '\r' || c == ' ' || c == '\t' {
@@ -154,7 +224,11 @@ impl Scanner {
             },
             c => {
                 return Some(Err(LoxError::ScannerError(ErrorData {
-                    message: format!("Unidentified character {}", c),
+                    message: if self.dialect.is_book() {
+                        String::from("Unexpected character.")
+                    } else {
+                        format!("Unidentified character {}", c)
+                    },
                     line_no: self.line_no,
                     location: String::new(),
                 })))
@@ -163,6 +237,58 @@ impl Scanner {
         Some(Ok(TokenMeta::new(token, self.line_no)))
     }
 
+    /// Scan the characters whose meaning differs in the book dialect,
+    /// which has none of the extended operators and no escapes or interpolation in strings.
+    /// Returns None for characters that are scanned the same way in both dialects
+    fn next_book_token(&mut self, c: char) -> Option<Result<Token, LoxError>> {
+        Some(Ok(match c {
+            '.' => Token::Dot,
+            '-' => Token::Minus,
+            '+' => Token::Plus,
+            '*' => Token::Star,
+            '/' if self.peek_char() != Some('/') => Token::Slash,
+            '=' => if_peek_eq!(self, '=', Token::EqualEqual, Token::Equal),
+            '<' => if_peek_eq!(self, '=', Token::LesserEqual, Token::Lesser),
+            '>' => if_peek_eq!(self, '=', Token::GreaterEqual, Token::Greater),
+            '"' => {
+                let start = self.current;
+                while self.peek_char() != Some('"') {
+                    if self.next_char().is_none() {
+                        return Some(Err(LoxError::ScannerError(ErrorData {
+                            message: String::from("Unterminated string."),
+                            line_no: self.line_no,
+                            location: String::new(),
+                        })));
+                    }
+                }
+                let string = self.src[start..self.current].iter().collect::<String>();
+                self.next_char(); // the closing '"'
+                Token::Literal(Literal::StringT(string))
+            }
+            c if c.is_ascii_digit() => match self.match_numeric() {
+                Ok(Token::Literal(Literal::Integer(i))) => Token::Literal(Literal::Number(i as f64)),
+                Ok(Token::Literal(Literal::BigInteger(i))) => {
+                    Token::Literal(Literal::Number(integer::to_f64(&i)))
+                }
+                other => return Some(other),
+            },
+            c if can_start_identifier(c) => match self.match_identifier() {
+                Ok(Token::Literal(literal)) => Token::Literal(literal),
+                Ok(keyword) if BOOK_KEYWORDS.contains(&&keyword.to_string()[..]) => keyword,
+                Ok(keyword) => Token::Literal(Literal::Identifier(keyword.to_string())),
+                Err(e) => return Some(Err(e)),
+            },
+            '[' | ']' | '?' | ':' | '%' | '&' | '|' | '^' | '~' => {
+                return Some(Err(LoxError::ScannerError(ErrorData {
+                    message: String::from("Unexpected character."),
+                    line_no: self.line_no,
+                    location: String::new(),
+                })))
+            }
+            _ => return None,
+        }))
+    }
+
     /// Get the index of the next occurrence of c if it exists in the string
     fn find_next(&self, c: char) -> Option<usize> {
         let mut curr = self.current + 1;
@@ -179,6 +305,7 @@ impl Scanner {
     /// note: returns a TokenMeta because the line number can change while scanning
     fn match_string(&mut self) -> Result<TokenMeta, LoxError> {
         let start_line = self.line_no;
+        let mut parts = vec![];
         let mut string = String::new();
         let mut is_escape = false;
         let mut ended = false;
@@ -191,6 +318,7 @@ impl Scanner {
                     'r' => '\r',
                     '\'' => '\'',
                     '"' => '"',
+                    '$' => '$',
                     '\n' => continue,
                     _ => {
                         return Err(LoxError::ScannerError(ErrorData {
@@ -208,6 +336,11 @@ impl Scanner {
                         ended = true;
                         break;
                     }
+                    '$' if self.peek_char() == Some('{') => {
+                        self.next_char();
+                        parts.push(StringPart::Literal(std::mem::take(&mut string)));
+                        parts.push(StringPart::Interpolation(self.match_interpolation()?));
+                    }
                     _ => string.push(c),
                 }
             }
@@ -220,11 +353,40 @@ impl Scanner {
                 line_no: self.line_no,
                 location: String::new(),
             }))
-        } else {
+        } else if parts.is_empty() {
             Ok(TokenMeta::new(
                 Token::Literal(Literal::StringT(string)),
                 start_line,
             ))
+        } else {
+            parts.push(StringPart::Literal(string));
+            Ok(TokenMeta::new(Token::InterpolatedString(parts), start_line))
+        }
+    }
+
+    /// match the tokens of an interpolation up to its closing '}' (excluding leading '${')
+    fn match_interpolation(&mut self) -> Result<Vec<TokenMeta>, LoxError> {
+        let start_line = self.line_no;
+        let mut tokens = vec![];
+        let mut depth = 0;
+        loop {
+            let token = match self.next_token() {
+                Some(token) => token?,
+                None => {
+                    return Err(LoxError::ScannerError(ErrorData {
+                        message: String::from("Unterminated string interpolation"),
+                        line_no: start_line,
+                        location: String::new(),
+                    }))
+                }
+            };
+            match token.item() {
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace if depth == 0 => return Ok(tokens),
+                Token::RightBrace => depth -= 1,
+                _ => (),
+            }
+            tokens.push(token);
         }
     }
 
@@ -263,13 +425,17 @@ impl Scanner {
         let number_str = self.src[start..end].iter().collect::<String>();
 
         Ok(if is_int {
-            Token::Literal(Literal::Integer(number_str.parse().map_err(|_e| {
-                LoxError::ScannerError(ErrorData {
-                    message: format!("Could not parse integer from {}", number_str),
-                    line_no: self.line_no,
-                    location: String::new(),
-                })
-            })?))
+            // integers too large for an i64 become big integers
+            Token::Literal(match number_str.parse() {
+                Ok(i) => Literal::Integer(i),
+                Err(_) => integer::normalize(number_str.parse().map_err(|_e| {
+                    LoxError::ScannerError(ErrorData {
+                        message: format!("Could not parse integer from {}", number_str),
+                        line_no: self.line_no,
+                        location: String::new(),
+                    })
+                })?),
+            })
         } else {
             Token::Literal(Literal::Number(number_str.parse().map_err(|_e| {
                 LoxError::ScannerError(ErrorData {
@@ -291,11 +457,9 @@ impl Scanner {
             }
         }
         let word = self.src[start..self.current].iter().collect::<String>();
-        if let Some(token) = RESERVED_WORDS.get(&word[..]) {
-            Ok(token.clone())
-        } else {
-            Ok(Token::Literal(Literal::Identifier(word)))
-        }
+        Ok(RESERVED_WORDS
+            .with(|words| words.get(&word[..]).cloned())
+            .unwrap_or(Token::Literal(Literal::Identifier(word))))
     }
 }
 
@@ -0,0 +1,172 @@
+//! Runs every script in `examples/` with the interpreter and checks what it prints.
+//! Most examples test themselves and finish by printing "All tests passed";
+//! the rest are demos whose output is kept in `tests/expected/<name>.out`
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const PASSED: &str = "All tests passed\n";
+
+/// What running an example should produce
+enum Expect {
+    /// a self-checking script, run with the given extra arguments
+    Passes(&'static [&'static str]),
+    /// a demo whose stdout is in `tests/expected`, and which writes the given text to stderr
+    Output(&'static str),
+    /// a demo whose output changes from run to run, so only its success is checked
+    Runs,
+}
+
+fn run(name: &str, expect: Expect) {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join(format!("{}.lox", name));
+    let arguments = match expect {
+        Expect::Passes(arguments) => arguments,
+        _ => &[],
+    };
+    let output = Command::new(env!("CARGO_BIN_EXE_treelox"))
+        .args(arguments)
+        .arg(&script)
+        .output()
+        .expect("failed to run the interpreter");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{} exited with {}: {}", name, output.status, stderr);
+    match expect {
+        Expect::Passes(_) => {
+            assert!(stdout.ends_with(PASSED), "{} failed:\n{}{}", name, stdout, stderr);
+            assert_eq!(stderr, "", "{} wrote to stderr", name);
+        }
+        Expect::Output(expected_stderr) => {
+            let expected = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join("expected")
+                .join(format!("{}.out", name));
+            let expected = fs::read_to_string(expected).expect("missing expected output");
+            assert_eq!(stdout, expected, "unexpected output from {}", name);
+            assert_eq!(stderr, expected_stderr, "unexpected errors from {}", name);
+        }
+        Expect::Runs => {}
+    }
+}
+
+// examples named after Rust keywords get a trailing underscore
+macro_rules! examples {
+    ($($name:ident: $expect:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                run(stringify!($name).trim_end_matches('_'), $expect);
+            }
+        )*
+
+        /// new examples need an entry above, so that none of them is silently left out
+        #[test]
+        fn every_example_is_run() {
+            let names = [$(stringify!($name).trim_end_matches('_')),*];
+            let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+            for entry in fs::read_dir(examples).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension().map_or(false, |extension| extension == "lox") {
+                    let name = path.file_stem().unwrap().to_string_lossy();
+                    assert!(names.contains(&&name[..]), "examples/{}.lox is not run", name);
+                }
+            }
+        }
+    };
+}
+
+examples! {
+    bigints: Expect::Passes(&[]),
+    blocks: Expect::Output(""),
+    book: Expect::Passes(&["--dialect=book"]),
+    break_: Expect::Passes(&[]),
+    classes: Expect::Passes(&[]),
+    clock: Expect::Runs,
+    closures: Expect::Passes(&[]),
+    compound: Expect::Passes(&[]),
+    constants: Expect::Passes(&[]),
+    destructuring: Expect::Passes(&[]),
+    enums: Expect::Passes(&[]),
+    escape_str: Expect::Output(""),
+    exceptions: Expect::Passes(&[]),
+    expressions: Expect::Output(""),
+    for_: Expect::Output(""),
+    functions: Expect::Passes(&[]),
+    generators: Expect::Passes(&[]),
+    hello: Expect::Output("Line 12: Variable 'name' referenced before assignment\n"),
+    if_: Expect::Output(""),
+    interpolation: Expect::Passes(&[]),
+    iteration: Expect::Passes(&[]),
+    lambdas: Expect::Passes(&[]),
+    lists: Expect::Passes(&[]),
+    logical: Expect::Passes(&[]),
+    maps: Expect::Passes(&[]),
+    match_: Expect::Passes(&[]),
+    modules: Expect::Passes(&[]),
+    operators: Expect::Passes(&[]),
+    parameters: Expect::Passes(&[]),
+    strings: Expect::Passes(&[]),
+    types: Expect::Passes(&[]),
+    while_: Expect::Output(""),
+}
+
+/// Run a script written to a temporary file, giving its exit code, stdout and stderr
+fn run_source(name: &str, source: &str, arguments: &[&str]) -> (Option<i32>, String, String) {
+    let script = std::env::temp_dir().join(format!("treelox-{}-{}.lox", name, std::process::id()));
+    fs::write(&script, source).expect("failed to write the script");
+    let output = Command::new(env!("CARGO_BIN_EXE_treelox"))
+        .args(arguments)
+        .arg(&script)
+        .output()
+        .expect("failed to run the interpreter");
+    fs::remove_file(&script).ok();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+// the book dialect reports errors and prints numbers as the reference implementation does
+#[test]
+fn book_compile_errors() {
+    let cases = [
+        ("print 1", "[line 1] Error at end: Expect ';' after value."),
+        ("print 1;\nprint 2 +;", "[line 2] Error at ';': Expect expression."),
+        ("var 1 = 2;", "[line 1] Error at '1': Expect variable name."),
+        ("a + b = c;", "[line 1] Error at '=': Invalid assignment target."),
+        ("print (1;", "[line 1] Error at ';': Expect ')' after expression."),
+        ("if 1) print 1;", "[line 1] Error at '1': Expect '(' after 'if'."),
+        ("fun f(a, 1) {}", "[line 1] Error at '1': Expect parameter name."),
+        ("class A { m() {}", "[line 1] Error at end: Expect '}' after class body."),
+        ("print \"a\" \"b\";", "[line 1] Error at '\"b\"': Expect ';' after value."),
+        ("return 1;", "[line 1] Error at 'return': Can't return from top-level code."),
+        ("class A < A {}", "[line 1] Error at 'A': A class can't inherit from itself."),
+        (
+            "{ var a = 1; var a = 2; }",
+            "[line 1] Error at 'a': Already a variable with this name in this scope.",
+        ),
+        (
+            "{ var a = a; }",
+            "[line 1] Error at 'a': Can't read local variable in its own initializer.",
+        ),
+        ("print this;", "[line 1] Error at 'this': Can't use 'this' outside of a class."),
+    ];
+    for (index, (source, expected)) in cases.iter().enumerate() {
+        let name = format!("compile-error-{}", index);
+        let (code, _, stderr) = run_source(&name, source, &["--dialect=book"]);
+        assert_eq!(code, Some(65), "wrong exit code for {:?}", source);
+        assert_eq!(stderr.trim_end(), *expected, "wrong error for {:?}", source);
+    }
+}
+
+#[test]
+fn book_printing() {
+    let source = "print 3 / 0;\nprint -3 / 0;\nprint 0 / 0;\nprint 1.5 * 2;\nprint clock;";
+    let (code, stdout, _) = run_source("printing", source, &["--dialect=book"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "Infinity\n-Infinity\nNaN\n3\n<native fn>\n");
+}